@@ -1,5 +1,6 @@
+use anyhow::Result;
 use async_trait::async_trait;
-use furbooru::{Client, Comment, Forum, Image, Post, Result, Topic};
+use furbooru::{Client, Comment, Forum, Image, Post, Topic};
 
 #[tokio::main]
 async fn main() -> Result<()> {
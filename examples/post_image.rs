@@ -1,4 +1,5 @@
-use furbooru::{Client, Image, ImageMeta, Result};
+use anyhow::Result;
+use furbooru::{Client, ImageMeta};
 
 #[tokio::main]
 async fn main() -> Result<()> {
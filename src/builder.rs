@@ -0,0 +1,214 @@
+use crate::{
+    Client, Error, RateLimiter, Result, RetryPolicy, APP_USER_AGENT, DERPIBOORU_BASE, FURBOORU_BASE,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::time::Duration;
+
+/// Configures and creates a [`Client`].
+///
+/// ```
+/// let cli = furbooru::Client::builder()
+///     .derpibooru()
+///     .user_agent("my-bot/0.1 (Alicia)")
+///     .key("42069")
+///     .timeout(std::time::Duration::from_secs(30))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct ClientBuilder {
+    api_base: Option<String>,
+    user_agent: Option<String>,
+    key: Option<String>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    default_headers: HeaderMap,
+    proxies: Vec<reqwest::Proxy>,
+    root_certificates: Vec<reqwest::Certificate>,
+    client: Option<reqwest::Client>,
+    limiter: Option<RateLimiter>,
+    retry: Option<RetryPolicy>,
+}
+
+impl ClientBuilder {
+    /// Create a builder targeting Furbooru with no API key.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Target any server you want. A trailing slash will be added if it is missing.
+    pub fn base_url<T: Into<String>>(mut self, api_base: T) -> Self {
+        let mut api_base = api_base.into();
+        if !api_base.ends_with('/') {
+            api_base.push('/');
+        }
+        self.api_base = Some(api_base);
+        self
+    }
+
+    /// Target Furbooru. This is the default.
+    pub fn furbooru(self) -> Self {
+        self.base_url(FURBOORU_BASE)
+    }
+
+    /// Target Derpibooru.
+    pub fn derpibooru(self) -> Self {
+        self.base_url(DERPIBOORU_BASE)
+    }
+
+    /// Set the user agent of your bot. The library's own user agent is appended to it.
+    pub fn user_agent<T: Into<String>>(mut self, user_agent: T) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Set the API key to send with every request. Without one (or with an empty one)
+    /// the client is anonymous.
+    pub fn key<T: Into<String>>(mut self, key: T) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Set the total timeout for each request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for connecting to the booru.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Add a header that will be sent with every request.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.insert(name, value);
+        self
+    }
+
+    /// Send requests through a proxy.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Trust an additional root certificate.
+    pub fn add_root_certificate(mut self, cert: reqwest::Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    /// Use a preconfigured `reqwest::Client` for all requests.
+    ///
+    /// The user agent, timeouts, default headers, proxies and root certificates set on
+    /// this builder are ignored when you do this; configure them on your client instead.
+    pub fn reqwest_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Throttle requests with a client-side rate limiter. Requests are not throttled
+    /// by default. The limiter can be shared with other clients by cloning it.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Retry failed read requests. Requests are not retried by default.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Create the [`Client`]. This fails with [`Error::Url`] if the base URL isn't an
+    /// absolute URL that paths can be added to.
+    pub fn build(self) -> Result<Client> {
+        let api_base = url::Url::parse(self.api_base.as_deref().unwrap_or(FURBOORU_BASE))?;
+        if api_base.cannot_be_a_base() {
+            return Err(Error::Url(
+                url::ParseError::RelativeUrlWithCannotBeABaseBase,
+            ));
+        }
+
+        let cli = match self.client {
+            Some(cli) => cli,
+            None => {
+                let user_agent = match self.user_agent {
+                    Some(user_agent) => format!("{} {}", user_agent, APP_USER_AGENT),
+                    None => APP_USER_AGENT.to_string(),
+                };
+                let mut builder = reqwest::Client::builder()
+                    .user_agent(user_agent)
+                    .default_headers(self.default_headers);
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                for proxy in self.proxies {
+                    builder = builder.proxy(proxy);
+                }
+                for cert in self.root_certificates {
+                    builder = builder.add_root_certificate(cert);
+                }
+                builder.build()?
+            }
+        };
+
+        Ok(Client {
+            cli,
+            token: self.key.filter(|key| !key.is_empty()),
+            api_base,
+            limiter: self.limiter,
+            retry: self.retry,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    #[tokio::test]
+    async fn default_headers() {
+        let _ = pretty_env_logger::try_init();
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/foo"),
+                request::headers(contains(("x-bot-owner", "Alicia"))),
+                request::query(url_decoded(contains(("key", "42069")))),
+            ])
+            .respond_with(status_code(200)),
+        );
+
+        let cli = crate::Client::builder()
+            .base_url(server.url_str(""))
+            .key("42069")
+            .default_header(
+                reqwest::header::HeaderName::from_static("x-bot-owner"),
+                reqwest::header::HeaderValue::from_static("Alicia"),
+            )
+            .build()
+            .unwrap();
+        let resp = cli
+            .request(reqwest::Method::GET, "foo")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(200, resp.status().as_u16());
+    }
+
+    #[test]
+    fn invalid_base_url() {
+        for base in &["not a url", "mailto:alicia@example.com"] {
+            let result = crate::Client::builder().base_url(*base).build();
+            assert!(matches!(result, Err(Error::Url(_))), "{}", base);
+        }
+    }
+}
@@ -1,4 +1,10 @@
-use crate::Result;
+use crate::{
+    paginate::paginate,
+    search::{CommentSearch, SearchPage},
+    timestamp::Timestamp,
+    Extra, Result,
+};
+use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,35 +15,42 @@ pub(crate) struct Response {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ResponseList {
     pub comments: Vec<Comment>,
+    #[serde(default)]
+    pub total: Option<u64>,
 }
 
 /// A comment on an image.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Comment {
     pub author: String,
     pub avatar: String,
     pub body: String,
-    pub created_at: String,
-    pub edit_reason: ::serde_json::Value,
-    pub edited_at: ::serde_json::Value,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: Timestamp,
+    pub edit_reason: Option<String>,
+    #[serde(with = "crate::timestamp::option")]
+    pub edited_at: Option<Timestamp>,
     pub id: i64,
     pub image_id: i64,
-    pub updated_at: String,
+    #[serde(with = "crate::timestamp")]
+    pub updated_at: Timestamp,
     pub user_id: Option<i64>,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
 }
 
 impl crate::Client {
     /// Fetch an individual comment by ID.
     pub async fn comment(&self, id: u64) -> Result<Comment> {
         let resp: Response = self
-            .request(
+            .execute(self.request(
                 reqwest::Method::GET,
                 &format!("api/v1/json/comments/{}", id),
-            )
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            ))
             .await?;
         Ok(resp.comment)
     }
@@ -48,16 +61,50 @@ impl crate::Client {
         query: T,
         page: u64,
     ) -> Result<Vec<Comment>> {
-        let mut req = self
-            .request(reqwest::Method::GET, "api/v1/json/search/comments")
-            .query(&[("q", query.into())]);
+        Ok(self
+            .search_comments(CommentSearch::new(query).page(page))
+            .await?
+            .items)
+    }
 
-        if page != 0 {
-            req = req.query(&[("page", format!("{}", page))])
-        }
+    /// Search for comments with paging options.
+    pub async fn search_comments<S: Into<CommentSearch>>(
+        &self,
+        search: S,
+    ) -> Result<SearchPage<Comment>> {
+        let search = search.into();
+        let resp = self.comment_search_page(&search).await?;
+        Ok(SearchPage::new(
+            resp.comments,
+            resp.total,
+            search.page,
+            search.per_page,
+            None,
+        ))
+    }
+
+    /// Search for comments, lazily fetching every page of results starting at the
+    /// search's page.
+    pub fn comment_search_stream<S: Into<CommentSearch>>(
+        &self,
+        search: S,
+    ) -> impl Stream<Item = Result<Comment>> + '_ {
+        let search = search.into();
+        paginate(search.page.unwrap_or(1), search.per_page, move |page| {
+            let search = search.clone().page(page);
+            async move {
+                let resp = self.comment_search_page(&search).await?;
+                Ok((resp.comments, resp.total))
+            }
+        })
+    }
+
+    async fn comment_search_page(&self, search: &CommentSearch) -> Result<ResponseList> {
+        let req = self
+            .request(reqwest::Method::GET, "api/v1/json/search/comments")
+            .query(&search.params());
 
-        let resp: ResponseList = req.send().await?.error_for_status()?.json().await?;
-        Ok(resp.comments)
+        self.execute(req).await
     }
 }
 
@@ -94,6 +141,39 @@ mod tests {
 
         let cli =
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
-        cli.comment_search("*", 0).await.unwrap();
+        let comments = cli.comment_search("*", 0).await.unwrap();
+        assert!(comments
+            .iter()
+            .any(|c| c.edit_reason.as_deref() == Some("Original vid instead of reupload")));
+    }
+
+    #[tokio::test]
+    async fn comment_search_stream() {
+        use futures_util::{StreamExt, TryStreamExt};
+
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_comments.json")).unwrap();
+        let server = Server::run();
+        for page in &["2", "3"] {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/api/v1/json/search/comments"),
+                    request::query(url_decoded(contains(("page", *page)))),
+                ])
+                .times(1)
+                .respond_with(json_encoded(data.clone())),
+            );
+        }
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let comments: Vec<crate::Comment> = cli
+            .comment_search_stream(crate::CommentSearch::new("*").page(2))
+            .take(30)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(comments.len(), 30);
     }
 }
@@ -0,0 +1,311 @@
+use crate::{Error, Image, RequestKind, Result};
+use reqwest::{header, StatusCode};
+use sha2::{Digest, Sha512};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// How many times a download is resumed after its connection drops before giving up.
+const MAX_RESUMES: u32 = 5;
+
+/// One of the sizes an image is served in. See [`Representations`](crate::Representations).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Representation {
+    /// The full size file, as the booru stores it.
+    Full,
+    Large,
+    Medium,
+    Small,
+    Tall,
+    Thumb,
+    ThumbSmall,
+    ThumbTiny,
+}
+
+impl Representation {
+    /// The URL of this representation of `img`, if the booru has generated it yet.
+    pub fn url<'a>(&self, img: &'a Image) -> Option<&'a str> {
+        let reprs = match &img.representations {
+            Some(reprs) => reprs,
+            None if *self == Representation::Full && !img.view_url.is_empty() => {
+                return Some(&img.view_url)
+            }
+            None => return None,
+        };
+
+        Some(match self {
+            Representation::Full => &reprs.full,
+            Representation::Large => &reprs.large,
+            Representation::Medium => &reprs.medium,
+            Representation::Small => &reprs.small,
+            Representation::Tall => &reprs.tall,
+            Representation::Thumb => &reprs.thumb,
+            Representation::ThumbSmall => &reprs.thumb_small,
+            Representation::ThumbTiny => &reprs.thumb_tiny,
+        })
+    }
+}
+
+impl crate::Client {
+    /// Download a representation of an image into `out`, returning how many bytes
+    /// were written.
+    ///
+    /// Downloads count against the client's [`RateLimiter`](crate::RateLimiter) like
+    /// any other read, and failed requests are retried by its
+    /// [`RetryPolicy`](crate::RetryPolicy).
+    ///
+    /// If the connection drops partway through, the download picks up where it left
+    /// off with a `Range` request. When the full file is downloaded, it is checked
+    /// against the image's SHA-512 hashes and [`Error::HashMismatch`] is returned if
+    /// it matches neither. Whatever was written to `out` by then is left there.
+    ///
+    /// This fails with [`Error::NotFound`] if the booru hasn't generated the
+    /// representation yet.
+    pub async fn download_image<W>(
+        &self,
+        img: &Image,
+        repr: Representation,
+        out: &mut W,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let url = repr.url(img).ok_or(Error::NotFound)?;
+        let mut hasher = Sha512::new();
+        let mut written = 0;
+        let mut resumes = 0;
+
+        loop {
+            match self
+                .download_part(url, &mut written, &mut hasher, out)
+                .await
+            {
+                Ok(()) => break,
+                Err(Error::Transport(why)) if resumes < MAX_RESUMES => {
+                    resumes += 1;
+                    log::debug!(
+                        "download of {} dropped at {} bytes, resuming: {}",
+                        url,
+                        written,
+                        why
+                    );
+                }
+                Err(why) => return Err(why),
+            }
+        }
+        out.flush().await.map_err(Error::Io)?;
+
+        if repr == Representation::Full {
+            let actual = hex::encode(hasher.finalize());
+            let expected: Vec<String> = [&img.sha512_hash, &img.orig_sha512_hash]
+                .iter()
+                .filter(|hash| !hash.is_empty())
+                .map(|hash| hash.to_string())
+                .collect();
+            if !expected.is_empty()
+                && !expected
+                    .iter()
+                    .any(|hash| hash.eq_ignore_ascii_case(&actual))
+            {
+                return Err(Error::HashMismatch { expected, actual });
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Fetch `url` starting at byte `written`, appending what comes back to `out`.
+    /// `written` and `hasher` are kept up to date as data arrives so the caller can
+    /// resume from wherever this stopped.
+    async fn download_part<W>(
+        &self,
+        url: &str,
+        written: &mut u64,
+        hasher: &mut Sha512,
+        out: &mut W,
+    ) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let mut req = self.cli.get(url);
+        if *written != 0 {
+            req = req.header(header::RANGE, format!("bytes={}-", written));
+        }
+
+        let mut resp = self
+            .retrying(req.build()?, |req| async move {
+                let resp = self.dispatch(req, RequestKind::Read).await?;
+                if !resp.status().is_success() {
+                    return Err(Error::from_response(&resp));
+                }
+                Ok(resp)
+            })
+            .await?;
+        // A server that ignores the range starts over, so skip what we already have.
+        let mut skip = match resp.status() {
+            StatusCode::PARTIAL_CONTENT => 0,
+            _ => *written,
+        };
+
+        while let Some(chunk) = resp.chunk().await? {
+            let mut chunk = &chunk[..];
+            if skip != 0 {
+                let n = skip.min(chunk.len() as u64);
+                skip -= n;
+                chunk = &chunk[n as usize..];
+            }
+
+            out.write_all(chunk).await.map_err(Error::Io)?;
+            hasher.update(chunk);
+            *written += chunk.len() as u64;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Representation;
+    use crate::{Error, Image};
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use sha2::{Digest, Sha512};
+
+    const BODY: &[u8] = b"not really a png";
+
+    fn image(server: &Server) -> Image {
+        Image {
+            view_url: server.url_str("/img/view/2366.png"),
+            sha512_hash: hex::encode(Sha512::digest(BODY)),
+            ..Image::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn download_image() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/img/view/2366.png"))
+                .times(2)
+                .respond_with(status_code(200).body(BODY)),
+        );
+
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .build()
+            .unwrap();
+        let mut img = image(&server);
+
+        let mut out = Vec::new();
+        let n = cli
+            .download_image(&img, Representation::Full, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(n, BODY.len() as u64);
+        assert_eq!(out, BODY);
+
+        img.sha512_hash = "abad1dea".into();
+        let result = cli
+            .download_image(&img, Representation::Full, &mut Vec::new())
+            .await;
+        match result {
+            Err(Error::HashMismatch { expected, .. }) => assert_eq!(expected, vec!["abad1dea"]),
+            result => panic!("wanted a hash mismatch, got {:?}", result),
+        }
+
+        let result = cli
+            .download_image(&img, Representation::Thumb, &mut Vec::new())
+            .await;
+        assert!(matches!(result, Err(Error::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn limited_and_retried() {
+        use crate::{ratelimit::Quota, RateLimiter, RetryPolicy};
+        use std::time::Duration;
+
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/img/view/2366.png"))
+                .times(2)
+                .respond_with(cycle![status_code(503), status_code(200).body(BODY)]),
+        );
+
+        let limiter = RateLimiter::new(
+            Quota::new(10, Duration::from_secs(3600)),
+            Quota::per_second(1),
+        );
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .rate_limiter(limiter.clone())
+            .retry_policy(
+                RetryPolicy::new()
+                    .base_delay(Duration::from_millis(1))
+                    .jitter(false),
+            )
+            .build()
+            .unwrap();
+
+        let mut out = Vec::new();
+        cli.download_image(&image(&server), Representation::Full, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, BODY);
+        assert_eq!(limiter.status().read.available, 8);
+    }
+
+    #[tokio::test]
+    async fn resume() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/img/view/2366.png"),
+                request::headers(contains(("range", "bytes=4-"))),
+            ])
+            .respond_with(status_code(206).body(&BODY[4..])),
+        );
+
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .build()
+            .unwrap();
+        let url = server.url_str("/img/view/2366.png");
+
+        let mut hasher = Sha512::new();
+        hasher.update(&BODY[..4]);
+        let mut out = BODY[..4].to_vec();
+        let mut written = 4;
+        cli.download_part(&url, &mut written, &mut hasher, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, BODY);
+        assert_eq!(written, BODY.len() as u64);
+        assert_eq!(hasher.finalize()[..], Sha512::digest(BODY)[..]);
+    }
+
+    #[tokio::test]
+    async fn resume_ignored() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/img/view/2366.png"))
+                .respond_with(status_code(200).body(BODY)),
+        );
+
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .build()
+            .unwrap();
+        let url = server.url_str("/img/view/2366.png");
+
+        let mut hasher = Sha512::new();
+        hasher.update(&BODY[..4]);
+        let mut out = BODY[..4].to_vec();
+        let mut written = 4;
+        cli.download_part(&url, &mut written, &mut hasher, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, BODY);
+    }
+}
@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+/// A specialized `Result` type for booru API calls.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong when talking to a booru.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The thing you asked for does not exist (HTTP 404).
+    #[error("not found")]
+    NotFound,
+
+    /// The API key is missing, wrong or not allowed to do this (HTTP 401 or 403).
+    /// This holds the status the booru sent.
+    #[error("unauthorized ({0})")]
+    Unauthorized(reqwest::StatusCode),
+
+    /// [`Client::whoami`](crate::Client::whoami) couldn't tell who owns the API key,
+    /// either because nothing names the owner or because different things name
+    /// different users.
+    #[error("can't tell who owns this API key")]
+    UnknownIdentity,
+
+    /// This call needs an API key but the client is anonymous. Nothing was sent.
+    #[error("this call needs an API key")]
+    MissingKey,
+
+    /// The booru is rate limiting you (HTTP 429). `retry_after` is set when the
+    /// server told us how long to wait.
+    #[error("rate limited (retry after {retry_after:?})")]
+    RateLimited { retry_after: Option<Duration> },
+
+    /// The booru returned some other unsuccessful status code.
+    #[error("server returned {0}")]
+    Server(reqwest::StatusCode),
+
+    /// The response body could not be decoded into the expected type. The raw body is
+    /// kept around so schema drift can be reported upstream.
+    #[error("can't decode response: {source}")]
+    Decode {
+        body: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The request could not be sent or the response could not be read.
+    #[error("transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+
+    /// The firehose websocket failed.
+    #[error("firehose error: {0}")]
+    Firehose(Box<tokio_tungstenite::tungstenite::Error>),
+
+    /// A URL could not be parsed.
+    #[error("invalid url: {0}")]
+    Url(#[from] url::ParseError),
+
+    /// The booru rejected an upload. This lists what was wrong with it.
+    #[error("upload rejected: {0}")]
+    Upload(crate::upload::UploadError),
+
+    /// A downloaded file doesn't match the hashes the booru has for it. `expected`
+    /// holds every hash it was compared against.
+    #[error("hash mismatch: expected {}, got {actual}", .expected.join(" or "))]
+    HashMismatch {
+        expected: Vec<String>,
+        actual: String,
+    },
+
+    /// Reading or writing a local file failed.
+    #[error("i/o error: {0}")]
+    Io(#[source] std::io::Error),
+
+    /// A [`Mirror`](crate::mirror::Mirror) layout put an image outside the mirror
+    /// directory. This holds the path the layout returned.
+    #[error("mirror layout path {} is outside the mirror directory", .0.display())]
+    Layout(std::path::PathBuf),
+
+    /// A mirror's state file isn't valid. Delete it to start the mirror over.
+    #[error("invalid mirror state file {}: {source}", .path.display())]
+    State {
+        path: std::path::PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A search query could not be parsed.
+    #[error("invalid query: {0}")]
+    Query(#[from] crate::query::ParseError),
+
+    /// A search query could not be evaluated locally.
+    #[error("can't evaluate query: {0}")]
+    Eval(#[from] crate::matcher::EvalError),
+
+    /// A firehose callback returned an error.
+    #[error("firehose callback failed: {0}")]
+    Callback(#[source] anyhow::Error),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(why: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::Firehose(Box::new(why))
+    }
+}
+
+impl Error {
+    /// Classify an unsuccessful response by its status code.
+    pub(crate) fn from_response(resp: &reqwest::Response) -> Self {
+        use reqwest::StatusCode;
+
+        match resp.status() {
+            StatusCode::NOT_FOUND => Error::NotFound,
+            status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                Error::Unauthorized(status)
+            }
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                retry_after: resp
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|val| val.to_str().ok())
+                    .and_then(|val| val.trim().parse().ok())
+                    .map(Duration::from_secs),
+            },
+            status => Error::Server(status),
+        }
+    }
+
+    /// The HTTP status code associated with this error, if any.
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        use reqwest::StatusCode;
+
+        match self {
+            Error::NotFound => Some(StatusCode::NOT_FOUND),
+            Error::Unauthorized(status) => Some(*status),
+            Error::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            Error::Server(status) => Some(*status),
+            Error::Transport(why) => why.status(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn not_found() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/images/1"))
+                .respond_with(status_code(404)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        assert!(matches!(cli.image(1).await, Err(Error::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/images/1"))
+                .respond_with(status_code(429).insert_header("Retry-After", "5")),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        match cli.image(1).await {
+            Err(Error::RateLimited { retry_after }) => {
+                assert_eq!(retry_after, Some(Duration::from_secs(5)))
+            }
+            other => panic!("wanted rate limited error, got: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/images/1"))
+                .respond_with(status_code(403)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let why = cli.image(1).await.unwrap_err();
+        assert!(matches!(why, Error::Unauthorized(_)));
+        assert_eq!(why.status(), Some(reqwest::StatusCode::FORBIDDEN));
+    }
+
+    #[tokio::test]
+    async fn decode() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/images/1"))
+                .respond_with(status_code(200).body(r#"{"image": {"id": "lol"}}"#)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        match cli.image(1).await {
+            Err(Error::Decode { body, .. }) => assert_eq!(body, r#"{"image": {"id": "lol"}}"#),
+            other => panic!("wanted decode error, got: {:?}", other),
+        }
+    }
+}
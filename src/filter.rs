@@ -1,4 +1,5 @@
-use crate::Result;
+use crate::{paginate::paginate, query::Query, Extra, Image, Result};
+use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,10 +10,14 @@ pub(crate) struct Response {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ResponseList {
     pub filters: Vec<Filter>,
+    #[serde(default)]
+    pub total: Option<u64>,
 }
 
 /// An image filter.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Filter {
     pub description: String,
     pub hidden_complex: Option<String>,
@@ -20,22 +25,68 @@ pub struct Filter {
     pub id: i64,
     pub name: String,
     pub public: bool,
-    pub spoilered_complex: ::serde_json::Value,
+    pub spoilered_complex: Option<String>,
     pub spoilered_tag_ids: Vec<i64>,
     pub system: bool,
     pub user_count: i64,
-    pub user_id: ::serde_json::Value,
+    /// The user that made this filter. System filters have none.
+    pub user_id: Option<i64>,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
+}
+
+/// What a filter does to an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Visibility {
+    Visible,
+    /// The image is shown behind a spoiler.
+    Spoilered,
+    /// The image is not shown at all.
+    Hidden,
+}
+
+impl Filter {
+    /// Work out whether this filter hides or spoilers `img`, the same way the booru
+    /// would.
+    ///
+    /// The complex filters are parsed and evaluated locally, so this fails with
+    /// [`Error::Query`](crate::Error::Query) or [`Error::Eval`](crate::Error::Eval)
+    /// if they use syntax or fields that can't be evaluated outside the booru. See
+    /// [`matcher`](crate::matcher) for which fields those are.
+    pub fn visibility(&self, img: &Image) -> Result<Visibility> {
+        if has_any(&img.tag_ids, &self.hidden_tag_ids)
+            || complex_matches(self.hidden_complex.as_deref(), img)?
+        {
+            Ok(Visibility::Hidden)
+        } else if has_any(&img.tag_ids, &self.spoilered_tag_ids)
+            || complex_matches(self.spoilered_complex.as_deref(), img)?
+        {
+            Ok(Visibility::Spoilered)
+        } else {
+            Ok(Visibility::Visible)
+        }
+    }
+}
+
+fn has_any(tag_ids: &[i64], filtered: &[i64]) -> bool {
+    tag_ids.iter().any(|id| filtered.contains(id))
+}
+
+/// Blank complex filters match nothing.
+fn complex_matches(q: Option<&str>, img: &Image) -> Result<bool> {
+    match q.map(str::trim) {
+        None | Some("") => Ok(false),
+        Some(q) => Ok(Query::parse(q)?.matches(img)?),
+    }
 }
 
 impl crate::Client {
     /// Fetch a filter by its ID.
     pub async fn filter(&self, id: u64) -> Result<Filter> {
         let resp: Response = self
-            .request(reqwest::Method::GET, &format!("api/v1/json/filters/{}", id))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            .execute(self.request(reqwest::Method::GET, &format!("api/v1/json/filters/{}", id)))
             .await?;
         Ok(resp.filter)
     }
@@ -43,32 +94,44 @@ impl crate::Client {
     /// Fetch the list of system filters.
     pub async fn system_filters(&self) -> Result<Vec<Filter>> {
         let resp: ResponseList = self
-            .request(reqwest::Method::GET, "api/v1/json/filters/system")
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            .execute(self.request(reqwest::Method::GET, "api/v1/json/filters/system"))
             .await?;
 
         Ok(resp.filters)
     }
 
-    /// Fetch the list of user-level filters.
+    /// Fetch the list of user-level filters. This needs an API key.
     pub async fn user_filters(&self, page: u64) -> Result<Vec<Filter>> {
+        Ok(self.user_filters_page(page).await?.filters)
+    }
+
+    /// Fetch every user-level filter, lazily fetching each page starting at the one
+    /// [`Client::user_filters`](crate::Client::user_filters) would fetch. This needs
+    /// an API key.
+    pub fn user_filters_stream(&self, page: u64) -> impl Stream<Item = Result<Filter>> + '_ {
+        paginate(page, None, move |page| async move {
+            let resp = self.user_filters_page(page).await?;
+            Ok((resp.filters, resp.total))
+        })
+    }
+
+    async fn user_filters_page(&self, page: u64) -> Result<ResponseList> {
+        self.require_key()?;
+
         let mut req = self.request(reqwest::Method::GET, "api/v1/json/filters/user");
 
         if page != 0 {
             req = req.query(&[("page", format!("{}", page))])
         }
 
-        let resp: ResponseList = req.send().await?.error_for_status()?.json().await?;
-
-        Ok(resp.filters)
+        self.execute(req).await
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{Filter, Visibility};
+    use crate::Image;
     use httptest::{matchers::*, responders::*, Expectation, Server};
 
     #[tokio::test]
@@ -100,7 +163,10 @@ mod tests {
 
         let cli =
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
-        cli.system_filters().await.unwrap();
+        let filters = cli.system_filters().await.unwrap();
+        let spoilers = filters.iter().find(|f| f.id == 63).unwrap();
+        assert_eq!(spoilers.spoilered_complex.as_deref(), Some("score.lte:-5"));
+        assert!(filters.iter().all(|f| f.user_id.is_none()));
     }
 
     #[tokio::test]
@@ -118,4 +184,29 @@ mod tests {
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
         cli.user_filters(0).await.unwrap();
     }
+
+    #[test]
+    fn visibility() {
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/filter_1.json")).unwrap();
+        let mut filter: Filter = serde_json::from_value(data["filter"].clone()).unwrap();
+        let mut img = Image {
+            tag_ids: vec![40482, 2],
+            score: 10,
+            ..Image::default()
+        };
+        assert_eq!(filter.visibility(&img).unwrap(), Visibility::Spoilered);
+
+        img.tag_ids = vec![40482];
+        assert_eq!(filter.visibility(&img).unwrap(), Visibility::Visible);
+
+        img.score = -10;
+        assert_eq!(filter.visibility(&img).unwrap(), Visibility::Hidden);
+
+        filter.hidden_complex = Some("my:upvotes".into());
+        assert!(matches!(
+            filter.visibility(&img),
+            Err(crate::Error::Eval(_))
+        ));
+    }
 }
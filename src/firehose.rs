@@ -1,22 +1,26 @@
 use crate::*;
 use async_trait::async_trait;
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{
+    stream::{self, Stream},
+    SinkExt, StreamExt,
+};
 use http::{version::Version, Request};
 use serde::{Deserialize, Serialize};
 use tokio_tungstenite::{connect_async, tungstenite::protocol};
 
-const JOIN_EVENT: &'static str = r#"[0, 0, "firehose", "phx_join", {}]"#;
-const HEARTBEAT_EVENT: &'static str = r#"[0, 0, "phoenix", "heartbeat", {}]"#;
+const JOIN_EVENT: &str = r#"[0, 0, "firehose", "phx_join", {}]"#;
+const HEARTBEAT_EVENT: &str = r#"[0, 0, "phoenix", "heartbeat", {}]"#;
 
 /// This trait contains a series of hooks that will be called in response to various
 /// firehose events.
 ///
 /// You do not need to implement all of these hooks, just implement the ones that are
-/// important for your application.
+/// important for your application. Hooks can fail with any error; it will be passed
+/// back to the caller of [`Client::firehose`] as [`Error::Callback`].
 #[async_trait]
 pub trait FirehoseAdaptor {
     /// This responds to the `image:create` event, which fires when a new image is created.
-    async fn image_created(&self, _img: Image) -> Result<()> {
+    async fn image_created(&self, _img: Image) -> anyhow::Result<()> {
         Ok(())
     }
 
@@ -27,13 +31,13 @@ pub trait FirehoseAdaptor {
         _image_id: u64,
         _added: String,
         _removed: String,
-    ) -> Result<()> {
+    ) -> anyhow::Result<()> {
         Ok(())
     }
 
     /// This responds to the `image:process` event, which fires after an image is
     /// finished processing.
-    async fn image_processed(&self, _id: u64) -> Result<()> {
+    async fn image_processed(&self, _id: u64) -> anyhow::Result<()> {
         Ok(())
     }
 
@@ -44,7 +48,7 @@ pub trait FirehoseAdaptor {
         _id: u64,
         _added: Vec<String>,
         _removed: Vec<String>,
-    ) -> Result<()> {
+    ) -> anyhow::Result<()> {
         Ok(())
     }
 
@@ -55,31 +59,31 @@ pub trait FirehoseAdaptor {
         _id: u64,
         _added: Vec<String>,
         _removed: Vec<String>,
-    ) -> Result<()> {
+    ) -> anyhow::Result<()> {
         Ok(())
     }
 
     /// This responds to the `image:update` event, which fires after an image has been
     /// updated.
-    async fn image_updated(&self, _img: Image) -> Result<()> {
+    async fn image_updated(&self, _img: Image) -> anyhow::Result<()> {
         Ok(())
     }
 
     /// This responds to the `comment:create` event, which fires after a comment has been
     /// created.
-    async fn comment_created(&self, _cmt: Comment) -> Result<()> {
+    async fn comment_created(&self, _cmt: Comment) -> anyhow::Result<()> {
         Ok(())
     }
 
     /// This responds to the `comment:update` event, which fires after a comment has been
     /// updated.
-    async fn comment_updated(&self, _cmt: Comment) -> Result<()> {
+    async fn comment_updated(&self, _cmt: Comment) -> anyhow::Result<()> {
         Ok(())
     }
 
     /// This responds to the `post:create` event, which fires after a forum post has been
     /// created.
-    async fn post_created(&self, _frm: Forum, _top: Topic, _pst: Post) -> Result<()> {
+    async fn post_created(&self, _frm: Forum, _top: Topic, _pst: Post) -> anyhow::Result<()> {
         Ok(())
     }
 }
@@ -110,9 +114,157 @@ struct ImageSourceUpdateEvent {
     removed: Vec<String>,
 }
 
+/// The receiving half of the firehose websocket.
+type Connection = stream::SplitStream<
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+>;
+
+/// Sends heartbeats for as long as the firehose is open. The task is stopped when
+/// this is dropped, which closes the websocket.
+struct Heartbeat(tokio::task::JoinHandle<()>);
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+enum State {
+    Start(url::Url),
+    Open(Connection, Heartbeat),
+    Closed,
+}
+
+/// Open the websocket, join the firehose channel and start the heartbeat. Plain
+/// `http` bases are connected to over `ws`, everything else over `wss`.
+async fn connect(api_base: &url::Url) -> Result<(Connection, Heartbeat)> {
+    let mut u = api_base.join("socket/websocket?vsn=2.0.0")?;
+    let scheme = if u.scheme() == "http" { "ws" } else { "wss" };
+    u.set_scheme(scheme).unwrap();
+    log::debug!("{}", u);
+
+    let mut req = Request::builder()
+        .uri(u.to_string())
+        .header("Origin", api_base.as_str())
+        .body(())
+        .map_err(tokio_tungstenite::tungstenite::Error::from)?;
+
+    *req.version_mut() = Version::HTTP_11;
+
+    let (mut ws_stream, _) = connect_async(req).await?;
+    log::debug!("connected");
+
+    let msg = protocol::Message::text(JOIN_EVENT);
+    ws_stream.send(msg).await?;
+    log::debug!("sent join event {}", JOIN_EVENT);
+
+    let (mut sink, source) = ws_stream.split();
+
+    let heartbeat = tokio::spawn(async move {
+        let thirty_seconds = std::time::Duration::new(30, 0);
+        loop {
+            log::debug!("sent heartbeat event {}", HEARTBEAT_EVENT);
+            if let Err(why) = sink.send(protocol::Message::text(HEARTBEAT_EVENT)).await {
+                log::error!("error sending heartbeat: {:?}", why);
+                return;
+            }
+            tokio::time::sleep(thirty_seconds).await;
+        }
+    });
+
+    Ok((source, Heartbeat(heartbeat)))
+}
+
+/// Turn a websocket text frame into a [`Message`]. Frames that aren't firehose
+/// events, like join replies, are skipped.
+pub(crate) fn parse_frame(body: String) -> Option<Result<Message>> {
+    let val: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(val) => val,
+        Err(source) => return Some(Err(Error::Decode { body, source })),
+    };
+    let mut val = match val {
+        serde_json::Value::Array(val) if val.len() == 5 => val,
+        _ => {
+            log::debug!("value is not a 5 element array");
+            return None;
+        }
+    };
+
+    let payload = val.pop().unwrap();
+    let (kind, event) = match (val[2].as_str(), val[3].as_str()) {
+        (Some(kind), Some(event)) => (kind, event),
+        _ => {
+            log::debug!("val[2] and val[3] aren't strings");
+            return None;
+        }
+    };
+    log::debug!("{} {}", kind, event);
+    if kind != "firehose" || event == "phx_reply" {
+        return None;
+    }
+
+    Some(Message::from_event(event, payload).map_err(|source| Error::Decode { body, source }))
+}
+
 impl Client {
+    /// Subscribe to the firehose, yielding every site event as it happens.
+    ///
+    /// The websocket is opened when the stream is first polled and closed when it is
+    /// dropped. Events whose payload can't be decoded come through as
+    /// [`Error::Decode`] and the stream keeps going. If the connection fails, the
+    /// error is yielded and the stream ends.
+    ///
+    /// ```no_run
+    /// # async fn run(cli: furbooru::Client) -> furbooru::Result<()> {
+    /// use futures_util::StreamExt;
+    /// use furbooru::Message;
+    ///
+    /// let mut messages = Box::pin(cli.firehose_stream());
+    /// while let Some(msg) = messages.next().await {
+    ///     if let Message::ImageCreate(img) = msg? {
+    ///         println!("new image: {} {}", img.id, img.view_url);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn firehose_stream(&self) -> impl Stream<Item = Result<Message>> + Send + 'static {
+        stream::unfold(State::Start(self.api_base.clone()), |state| async move {
+            let (mut source, heartbeat) = match state {
+                State::Start(api_base) => match connect(&api_base).await {
+                    Ok(conn) => conn,
+                    Err(why) => return Some((Err(why), State::Closed)),
+                },
+                State::Open(source, heartbeat) => (source, heartbeat),
+                State::Closed => return None,
+            };
+
+            loop {
+                let msg = match source.next().await? {
+                    Ok(msg) => msg,
+                    Err(why) => return Some((Err(why.into()), State::Closed)),
+                };
+                log::debug!("got message: {:?}", msg);
+                if !msg.is_text() {
+                    continue;
+                }
+
+                let body = match msg.into_text() {
+                    Ok(body) => body,
+                    Err(why) => return Some((Err(why.into()), State::Closed)),
+                };
+                if let Some(result) = parse_frame(body) {
+                    return Some((result, State::Open(source, heartbeat)));
+                }
+            }
+        })
+    }
+
     /// On every new site event, call methods on the callback. Explode if the callback explodes.
     ///
+    /// This is built on [`Client::firehose_stream`]. Events that can't be decoded
+    /// are logged and skipped.
+    ///
     /// Here is an example Adaptor implementation:
     ///
     /// ```rust
@@ -122,220 +274,287 @@ impl Client {
     ///
     /// #[async_trait]
     /// impl furbooru::FirehoseAdaptor for Adaptor {
-    ///   async fn image_created(&self, img: Image) -> Result<()> {
+    ///   async fn image_created(&self, img: Image) -> anyhow::Result<()> {
     ///     println!("new image: {} {} {}", img.id, img.name, img.view_url);
     ///     Ok(())
     ///   }
     ///
-    ///   async fn comment_created(&self, cmt: Comment) -> Result<()> {
+    ///   async fn comment_created(&self, cmt: Comment) -> anyhow::Result<()> {
     ///     println!("new comment on image {}: {}", cmt.image_id, cmt.body);
     ///     Ok(())
     ///   }
     /// }
     /// ```
     pub async fn firehose(&self, callback: impl FirehoseAdaptor + std::marker::Sync) -> Result<()> {
-        let path = format!("{}socket/websocket?vsn=2.0.0", self.api_base);
-        let mut u = url::Url::parse(&path)?;
-        u.set_scheme("wss").unwrap();
-        log::debug!("{}", u);
+        let messages = self.firehose_stream();
+        futures_util::pin_mut!(messages);
+
+        while let Some(msg) = messages.next().await {
+            let msg = match msg {
+                Ok(msg) => msg,
+                Err(Error::Decode { body, source }) => {
+                    log::error!("bad json: {:?} {}", source, body);
+                    continue;
+                }
+                Err(why) => return Err(why),
+            };
 
-        let mut req = Request::builder()
-            .uri(u.to_string())
-            .header("Origin", self.api_base.clone())
-            .body(())?;
+            match msg {
+                Message::ImageCreate(img) => callback.image_created(img).await,
+                Message::ImageUpdate(img) => callback.image_updated(img).await,
+                Message::ImageProcess { image_id } => callback.image_processed(image_id).await,
+                Message::ImageTagUpdate {
+                    image_id,
+                    added,
+                    removed,
+                } => callback.image_tag_updated(image_id, added, removed).await,
+                Message::ImageSourceUpdate {
+                    image_id,
+                    added,
+                    removed,
+                } => {
+                    callback
+                        .image_source_updated(image_id, added, removed)
+                        .await
+                }
+                Message::ImageDescriptionUpdate {
+                    image_id,
+                    added,
+                    removed,
+                } => {
+                    callback
+                        .image_description_updated(image_id, added, removed)
+                        .await
+                }
+                Message::CommentCreate(cmt) => callback.comment_created(cmt).await,
+                Message::CommentUpdate(cmt) => callback.comment_updated(cmt).await,
+                Message::PostCreate { forum, topic, post } => {
+                    callback.post_created(forum, topic, post).await
+                }
+                Message::Unknown { event, payload } => {
+                    log::info!("unknown event {}: {}", event, payload);
+                    Ok(())
+                }
+            }
+            .map_err(Error::Callback)?;
+        }
 
-        *req.version_mut() = Version::HTTP_11;
+        Ok(())
+    }
+}
 
-        let (mut ws_stream, _) = connect_async(req).await?;
-        log::debug!("connected");
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct ForumPost {
+    forum: Forum,
+    topic: Topic,
+    post: Post,
+}
 
-        let msg = protocol::Message::text(JOIN_EVENT);
-        ws_stream.send(msg).await?;
-        log::debug!("sent join event {}", JOIN_EVENT);
+/// A firehose event. See [`Client::firehose_stream`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// `image:create`: a new image was uploaded.
+    ImageCreate(Image),
+    /// `image:update`: an image was changed.
+    ImageUpdate(Image),
+    /// `image:process`: an image finished processing.
+    ImageProcess { image_id: u64 },
+    /// `image:tag_update`: tags were added to or removed from an image.
+    ImageTagUpdate {
+        image_id: u64,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// `image:source_update`: sources were added to or removed from an image.
+    ImageSourceUpdate {
+        image_id: u64,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// `image:description_update`: the description of an image changed.
+    ImageDescriptionUpdate {
+        image_id: u64,
+        added: String,
+        removed: String,
+    },
+    /// `comment:create`: a comment was posted.
+    CommentCreate(Comment),
+    /// `comment:update`: a comment was edited.
+    CommentUpdate(Comment),
+    /// `post:create`: a forum post was made.
+    PostCreate {
+        forum: Forum,
+        topic: Topic,
+        post: Post,
+    },
+    /// An event this crate doesn't know about yet, with its raw payload.
+    Unknown {
+        event: String,
+        payload: serde_json::Value,
+    },
+}
 
-        let (sink, mut source) = ws_stream.split();
+impl Message {
+    fn from_event(event: &str, payload: serde_json::Value) -> serde_json::Result<Self> {
+        use serde_json::from_value;
 
-        tokio::spawn(async {
-            let sink = move || sink;
-            let mut sink = sink();
-            let thirty_seconds = std::time::Duration::new(30, 0);
-            loop {
-                log::debug!("sent heartbeat event {}", HEARTBEAT_EVENT);
-                if let Err(why) = sink.send(protocol::Message::text(HEARTBEAT_EVENT)).await {
-                    log::error!("error sending heartbeat: {:?}", why);
-                    return;
+        Ok(match event {
+            "image:create" => Message::ImageCreate(from_value::<image::Response>(payload)?.image),
+            "image:update" => Message::ImageUpdate(from_value::<image::Response>(payload)?.image),
+            "image:process" => {
+                let ev: ImageProcessedEvent = from_value(payload)?;
+                Message::ImageProcess {
+                    image_id: ev.image_id,
                 }
-                tokio::time::sleep(thirty_seconds).await;
             }
-        });
-
-        while let Some(msg) = source.next().await {
-            let msg = msg?;
-            log::debug!("got message: {:?}", msg);
-            if !msg.is_text() {
-                continue;
+            "image:tag_update" => {
+                let ev: ImageTagUpdatedEvent = from_value(payload)?;
+                Message::ImageTagUpdate {
+                    image_id: ev.image_id,
+                    added: ev.added,
+                    removed: ev.removed,
+                }
             }
-
-            let val: serde_json::Value = serde_json::from_str(&msg.into_text()?)?;
-            if !val.is_array() {
-                log::debug!("value is not array");
-                continue;
+            "image:source_update" => {
+                let ev: ImageSourceUpdateEvent = from_value(payload)?;
+                Message::ImageSourceUpdate {
+                    image_id: ev.image_id,
+                    added: ev.added,
+                    removed: ev.removed,
+                }
+            }
+            "image:description_update" => {
+                let ev: ImageDescriptionUpdateEvent = from_value(payload)?;
+                Message::ImageDescriptionUpdate {
+                    image_id: ev.image_id,
+                    added: ev.added,
+                    removed: ev.removed,
+                }
+            }
+            "comment:create" => {
+                Message::CommentCreate(from_value::<comment::Response>(payload)?.comment)
+            }
+            "comment:update" => {
+                Message::CommentUpdate(from_value::<comment::Response>(payload)?.comment)
+            }
+            "post:create" => {
+                let ev: ForumPost = from_value(payload)?;
+                Message::PostCreate {
+                    forum: ev.forum,
+                    topic: ev.topic,
+                    post: ev.post,
+                }
             }
+            _ => Message::Unknown {
+                event: event.to_string(),
+                payload,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_frame, Message};
+    use crate::Error;
+    use futures_util::{SinkExt, StreamExt, TryStreamExt};
+    use tokio_tungstenite::tungstenite::protocol;
+
+    fn frame(kind: &str, event: &str, payload: serde_json::Value) -> String {
+        serde_json::json!([null, null, kind, event, payload]).to_string()
+    }
+
+    #[test]
+    fn parse() {
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/image_2336.json")).unwrap();
+        match parse_frame(frame("firehose", "image:create", data)) {
+            Some(Ok(Message::ImageCreate(img))) => assert_eq!(img.id, 2366),
+            result => panic!("wanted an image, got {:?}", result),
+        }
 
-            let val = val.as_array().unwrap();
-            if val.len() != 5 {
-                log::debug!("value doesn't have right length");
-                continue;
+        let payload = serde_json::json!({"image_id": 42, "added": ["orca"], "removed": []});
+        assert_eq!(
+            parse_frame(frame("firehose", "image:tag_update", payload))
+                .unwrap()
+                .unwrap(),
+            Message::ImageTagUpdate {
+                image_id: 42,
+                added: vec!["orca".into()],
+                removed: vec![],
             }
+        );
 
-            if !val[2].is_string() && !val[3].is_string() {
-                log::debug!("val[2] and val[3] aren't strings");
-                continue;
+        let payload = serde_json::json!({"badge_id": 1});
+        assert_eq!(
+            parse_frame(frame("firehose", "badge:award", payload.clone()))
+                .unwrap()
+                .unwrap(),
+            Message::Unknown {
+                event: "badge:award".into(),
+                payload,
             }
+        );
+    }
 
-            let kind = val[2].as_str().unwrap();
-            let event = val[3].as_str().unwrap();
-            let obj = val[4].clone();
-            log::debug!("{} {}", kind, event);
-
-            match kind {
-                "firehose" => {
-                    match event {
-                        "phx_reply" => {}
-                        "comment:create" => {
-                            match serde_json::from_value::<comment::Response>(obj) {
-                                Ok(cmt) => callback.comment_created(cmt.comment).await?,
-                                Err(why) => log::error!(
-                                    "bad json: {} {}: {:?} {}",
-                                    kind,
-                                    event,
-                                    why,
-                                    val[4]
-                                ),
-                            };
-                        }
-                        "comment:update" => {
-                            match serde_json::from_value::<comment::Response>(obj) {
-                                Ok(cmt) => callback.comment_updated(cmt.comment).await?,
-                                Err(why) => log::error!(
-                                    "bad json: {} {}: {:?} {}",
-                                    kind,
-                                    event,
-                                    why,
-                                    val[4]
-                                ),
-                            };
-                        }
-                        "image:create" => match serde_json::from_value::<image::Response>(obj) {
-                            Ok(img) => callback.image_created(img.image).await?,
-                            Err(why) => {
-                                log::error!("bad json: {} {}: {:?} {}", kind, event, why, val[4])
-                            }
-                        },
-                        "image:description_update" => {
-                            match serde_json::from_value::<ImageDescriptionUpdateEvent>(obj) {
-                                Ok(idue) => {
-                                    callback
-                                        .image_description_updated(
-                                            idue.image_id,
-                                            idue.added,
-                                            idue.removed,
-                                        )
-                                        .await?
-                                }
-                                Err(why) => log::error!(
-                                    "bad json: {} {}: {:?} {}",
-                                    kind,
-                                    event,
-                                    why,
-                                    val[4]
-                                ),
-                            }
-                        }
-                        "image:process" => match serde_json::from_value::<ImageProcessedEvent>(obj)
-                        {
-                            Ok(ipe) => callback.image_processed(ipe.image_id).await?,
-                            Err(why) => {
-                                log::error!("bad json: {} {}: {:?} {}", kind, event, why, val[4])
-                            }
-                        },
-                        "image:source_update" => {
-                            match serde_json::from_value::<ImageSourceUpdateEvent>(obj) {
-                                Ok(isue) => {
-                                    callback
-                                        .image_source_updated(
-                                            isue.image_id,
-                                            isue.added,
-                                            isue.removed,
-                                        )
-                                        .await?
-                                }
-                                Err(why) => log::error!(
-                                    "bad json: {} {}: {:?} {}",
-                                    kind,
-                                    event,
-                                    why,
-                                    val[4]
-                                ),
-                            }
-                        }
-
-                        "image:tag_update" => {
-                            match serde_json::from_value::<ImageTagUpdatedEvent>(obj) {
-                                Ok(itue) => {
-                                    callback
-                                        .image_tag_updated(itue.image_id, itue.added, itue.removed)
-                                        .await?
-                                }
-                                Err(why) => log::error!(
-                                    "bad json: {} {}: {:?} {}",
-                                    kind,
-                                    event,
-                                    why,
-                                    val[4]
-                                ),
-                            }
-                        }
-                        "image:update" => match serde_json::from_value::<image::Response>(obj) {
-                            Ok(img) => callback.image_updated(img.image).await?,
-                            Err(why) => {
-                                log::error!("bad json: {} {}: {:?} {}", kind, event, why, val[4])
-                            }
-                        },
-                        "post:create" => match serde_json::from_value::<ForumPost>(obj) {
-                            Ok(ptf) => {
-                                callback
-                                    .post_created(ptf.forum, ptf.topic, ptf.post)
-                                    .await?
-                            }
-                            Err(why) => {
-                                log::error!("bad json: {} {}: {:?} {}", kind, event, why, val[4])
-                            }
-                        },
-                        _ => {
-                            log::info!("unknown event {}: {}", event, serde_json::to_string(&obj)?);
-                        }
-                    };
-                }
-                _ => continue,
-            };
-        }
+    #[test]
+    fn skipped() {
+        let reply = serde_json::json!({"status": "ok", "response": {}});
+        assert!(parse_frame(frame("firehose", "phx_reply", reply.clone())).is_none());
+        assert!(parse_frame(frame("phoenix", "phx_reply", reply)).is_none());
+        assert!(parse_frame("{}".into()).is_none());
 
-        Ok(())
+        let bad = frame("firehose", "image:process", serde_json::json!({}));
+        assert!(matches!(parse_frame(bad), Some(Err(Error::Decode { .. }))));
+        assert!(matches!(
+            parse_frame("[".into()),
+            Some(Err(Error::Decode { .. }))
+        ));
     }
-}
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct ForumPost {
-    forum: Forum,
-    topic: Topic,
-    post: Post,
-}
+    #[tokio::test]
+    async fn firehose_stream() {
+        let _ = pretty_env_logger::try_init();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let join = ws.next().await.unwrap().unwrap();
+            assert!(join.into_text().unwrap().contains("phx_join"));
 
-/// A firehose message.
-#[derive(Debug)]
-pub enum Message {
-    CommentCreate(crate::Comment),
-    ImageCreate(crate::Image),
-    ImageUpdate(crate::Image),
+            let events = [
+                frame("firehose", "phx_reply", serde_json::json!({})),
+                frame(
+                    "firehose",
+                    "image:process",
+                    serde_json::json!({"image_id": 1}),
+                ),
+                frame(
+                    "firehose",
+                    "image:process",
+                    serde_json::json!({"image_id": 2}),
+                ),
+            ];
+            for event in events {
+                ws.send(protocol::Message::text(event)).await.unwrap();
+            }
+            ws.close(None).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let cli = crate::Client::builder()
+            .base_url(format!("http://{}/", addr))
+            .build()
+            .unwrap();
+        let messages: Vec<Message> = cli.firehose_stream().try_collect().await.unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                Message::ImageProcess { image_id: 1 },
+                Message::ImageProcess { image_id: 2 },
+            ]
+        );
+        server.await.unwrap();
+    }
 }
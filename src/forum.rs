@@ -1,10 +1,11 @@
-use crate::Result;
+use crate::{Extra, Result};
 use serde::{Deserialize, Serialize};
 
+// The forum list isn't paginated: every forum comes back in one response, so the
+// `total` the booru sends next to it is always `forums.len()` and isn't kept.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ResponseList {
     pub forums: Vec<Forum>,
-    pub total: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -14,23 +15,25 @@ pub(crate) struct Response {
 
 /// A discussion forum.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Forum {
     pub description: String,
     pub name: String,
     pub post_count: i64,
     pub short_name: String,
     pub topic_count: i64,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
 }
 
 impl crate::Client {
-    /// Get the list of forums.
+    /// Get the list of forums. This is every forum; the list isn't paginated.
     pub async fn forums(&self) -> Result<Vec<Forum>> {
         let resp: ResponseList = self
-            .request(reqwest::Method::GET, "api/v1/json/forums")
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            .execute(self.request(reqwest::Method::GET, "api/v1/json/forums"))
             .await?;
         Ok(resp.forums)
     }
@@ -38,14 +41,10 @@ impl crate::Client {
     /// Get details about an individual forum by ID.
     pub async fn forum<T: Into<String>>(&self, id: T) -> Result<Forum> {
         let resp: Response = self
-            .request(
+            .execute(self.request(
                 reqwest::Method::GET,
                 &format!("api/v1/json/forums/{}", id.into()),
-            )
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            ))
             .await?;
         Ok(resp.forum)
     }
@@ -60,6 +59,7 @@ mod tests {
         let _ = pretty_env_logger::try_init();
         let data: serde_json::Value =
             serde_json::from_slice(include_bytes!("../testdata/forums.json")).unwrap();
+        let total = data["total"].as_u64().unwrap();
         let server = Server::run();
         server.expect(
             Expectation::matching(request::method_path("GET", "/api/v1/json/forums"))
@@ -68,7 +68,8 @@ mod tests {
 
         let cli =
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
-        cli.forums().await.unwrap();
+        let forums = cli.forums().await.unwrap();
+        assert_eq!(forums.len() as u64, total);
     }
 
     #[tokio::test]
@@ -0,0 +1,164 @@
+use crate::{
+    paginate::paginate,
+    search::{GallerySearch, ImageSearch, SearchPage},
+    Error, Extra, Image, Result,
+};
+use futures_util::stream::Stream;
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ResponseList {
+    pub galleries: Vec<Gallery>,
+    #[serde(default)]
+    pub total: Option<u64>,
+}
+
+/// A gallery of images curated by a user.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct Gallery {
+    pub id: i64,
+    pub title: String,
+    pub description: String,
+    pub spoiler_warning: Option<String>,
+    pub thumbnail_id: i64,
+    pub user: String,
+    pub user_id: i64,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
+}
+
+impl crate::Client {
+    /// Fetch a gallery by its ID.
+    pub async fn gallery(&self, id: u64) -> Result<Gallery> {
+        // There is no endpoint for single galleries, so search for it instead.
+        let resp = self
+            .gallery_search_page(&GallerySearch::new(format!("id:{}", id)))
+            .await?;
+        resp.galleries.into_iter().next().ok_or(Error::NotFound)
+    }
+
+    /// Search for galleries.
+    pub async fn search_galleries<S: Into<GallerySearch>>(
+        &self,
+        search: S,
+    ) -> Result<SearchPage<Gallery>> {
+        let search = search.into();
+        let resp = self.gallery_search_page(&search).await?;
+        Ok(SearchPage::new(
+            resp.galleries,
+            resp.total,
+            search.page,
+            search.per_page,
+            None,
+        ))
+    }
+
+    /// Search for galleries, lazily fetching every page of results starting at the
+    /// search's page.
+    pub fn gallery_search_stream<S: Into<GallerySearch>>(
+        &self,
+        search: S,
+    ) -> impl Stream<Item = Result<Gallery>> + '_ {
+        let search = search.into();
+        paginate(search.page.unwrap_or(1), search.per_page, move |page| {
+            let search = search.clone().page(page);
+            async move {
+                let resp = self.gallery_search_page(&search).await?;
+                Ok((resp.galleries, resp.total))
+            }
+        })
+    }
+
+    /// Lazily fetch every image in a gallery, in the gallery's order. See
+    /// [`ImageSearch::gallery`] for how that order is picked, and to customize it.
+    pub fn gallery_images(&self, gallery_id: u64) -> impl Stream<Item = Result<Image>> + '_ {
+        self.image_search_stream(ImageSearch::gallery(gallery_id))
+    }
+
+    async fn gallery_search_page(&self, search: &GallerySearch) -> Result<ResponseList> {
+        let req = self
+            .request(reqwest::Method::GET, "api/v1/json/search/galleries")
+            .query(&search.params());
+
+        self.execute(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    // There's no captured gallery search response in testdata, so these only check
+    // the requests that are sent and answer them with an empty page.
+    #[tokio::test]
+    async fn gallery() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/galleries"),
+                request::query(url_decoded(contains(("q", "id:12")))),
+            ])
+            .respond_with(json_encoded(
+                serde_json::json!({"galleries": [], "total": 0}),
+            )),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        assert!(matches!(cli.gallery(12).await, Err(crate::Error::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn search_galleries() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/galleries"),
+                request::query(url_decoded(contains(("q", "title:orcas")))),
+                request::query(url_decoded(contains(("page", "2")))),
+            ])
+            .respond_with(json_encoded(
+                serde_json::json!({"galleries": [], "total": 0}),
+            )),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let page = cli
+            .search_galleries(crate::GallerySearch::new("title:orcas").page(2))
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+        assert_eq!(page.total, 0);
+    }
+
+    #[tokio::test]
+    async fn gallery_images() {
+        use futures_util::TryStreamExt;
+
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_images.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/images"),
+                request::query(url_decoded(contains(("q", "gallery_id:12")))),
+                request::query(url_decoded(contains(("sf", "gallery_id:12")))),
+                request::query(url_decoded(contains(("sd", "desc")))),
+            ])
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let images: Vec<crate::Image> = cli.gallery_images(12).try_collect().await.unwrap();
+        assert_eq!(images.len(), 4);
+    }
+}
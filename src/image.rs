@@ -1,4 +1,11 @@
-use crate::Result;
+use crate::{
+    paginate::paginate,
+    search::{ImageSearch, SearchPage},
+    timestamp::Timestamp,
+    upload::upload_response,
+    Error, Extra, Result,
+};
+use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -9,6 +16,10 @@ pub(crate) struct Response {
 #[derive(Serialize, Deserialize)]
 pub(crate) struct ResponseList {
     pub images: Vec<Image>,
+    #[serde(default)]
+    pub total: Option<u64>,
+    #[serde(default)]
+    pub interactions: Option<Vec<Interaction>>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -19,11 +30,14 @@ pub struct ImageMeta {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Image {
     pub name: String,
     pub faves: i64,
     pub format: String,
-    pub updated_at: String,
+    #[serde(with = "crate::timestamp")]
+    pub updated_at: Timestamp,
     pub downvotes: i64,
     pub duplicate_of: Option<u64>,
     pub tag_count: i64,
@@ -32,7 +46,8 @@ pub struct Image {
     pub deletion_reason: Option<String>,
     pub width: i64,
     pub processed: bool,
-    pub created_at: String,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: Timestamp,
     pub orig_sha512_hash: String,
     pub view_url: String,
     pub uploader_id: Option<i64>,
@@ -42,7 +57,8 @@ pub struct Image {
     pub mime_type: String,
     pub tag_ids: Vec<i64>,
     pub wilson_score: f64,
-    pub first_seen_at: String,
+    #[serde(with = "crate::timestamp")]
+    pub first_seen_at: Timestamp,
     pub tags: Vec<String>,
     pub id: i64,
     pub upvotes: i64,
@@ -54,6 +70,20 @@ pub struct Image {
     pub sha512_hash: String,
     pub source_url: Option<String>,
     pub description: String,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
+}
+
+/// Something the user that owns the API key did to an image, such as faving it or
+/// voting on it.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub image_id: i64,
+    pub user_id: i64,
+    pub interaction_type: String,
+    pub value: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -80,11 +110,7 @@ impl crate::Client {
     /// Get information about the currently featured image.
     pub async fn featured_image(&self) -> Result<Image> {
         let resp: Response = self
-            .request(reqwest::Method::GET, "api/v1/json/images/featured")
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            .execute(self.request(reqwest::Method::GET, "api/v1/json/images/featured"))
             .await?;
 
         Ok(resp.image)
@@ -93,11 +119,7 @@ impl crate::Client {
     /// Get information about an image by ID.
     pub async fn image(&self, id: u64) -> Result<Image> {
         let resp: Response = self
-            .request(reqwest::Method::GET, &format!("api/v1/json/images/{}", id))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            .execute(self.request(reqwest::Method::GET, &format!("api/v1/json/images/{}", id)))
             .await?;
 
         Ok(resp.image)
@@ -105,25 +127,68 @@ impl crate::Client {
 
     /// Search for images that match a set of tags.
     pub async fn image_search<T: Into<String>>(&self, q: T, page: u64) -> Result<Vec<Image>> {
-        let mut req = self
-            .request(reqwest::Method::GET, &format!("api/v1/json/search/images"))
-            .query(&[("q", q.into())]);
+        Ok(self
+            .search_images(ImageSearch::new(q).page(page))
+            .await?
+            .items)
+    }
 
-        if page != 0 {
-            req = req.query(&[("page", format!("{}", page))]);
-        }
+    /// Search for images with sorting, paging and filter options.
+    pub async fn search_images<S: Into<ImageSearch>>(
+        &self,
+        search: S,
+    ) -> Result<SearchPage<Image>> {
+        let search = search.into();
+        let resp = self.image_search_page(&search).await?;
+        Ok(SearchPage::new(
+            resp.images,
+            resp.total,
+            search.page,
+            search.per_page,
+            resp.interactions,
+        ))
+    }
 
-        let resp: ResponseList = req.send().await?.error_for_status()?.json().await?;
+    /// Search for images, lazily fetching every page of results starting at the
+    /// search's page. Use [`StreamExt::take`] to limit how many images are fetched.
+    ///
+    /// [`StreamExt::take`]: futures_util::StreamExt::take
+    pub fn image_search_stream<S: Into<ImageSearch>>(
+        &self,
+        search: S,
+    ) -> impl Stream<Item = Result<Image>> + '_ {
+        let search = search.into();
+        paginate(search.page.unwrap_or(1), search.per_page, move |page| {
+            let search = search.clone().page(page);
+            async move {
+                let resp = self.image_search_page(&search).await?;
+                Ok((resp.images, resp.total))
+            }
+        })
+    }
+
+    async fn image_search_page(&self, search: &ImageSearch) -> Result<ResponseList> {
+        let req = self
+            .request(reqwest::Method::GET, "api/v1/json/search/images")
+            .query(&search.params());
 
-        Ok(resp.images)
+        self.execute(req).await
     }
 
     /// Upload an image to the booru.
     ///
     /// Test this call with a custom instance of philomena. Abuse of this call will
     /// likely result in a ban from the booru you are posting things to.
+    ///
+    /// If the booru rejects the image, this fails with [`Error::Upload`] saying
+    /// why.
     pub async fn post_image(&self, image_url: String, im: ImageMeta) -> Result<Image> {
-        self.cli.get(&image_url).send().await?.error_for_status()?;
+        self.require_key()?;
+
+        let resp = self.cli.get(&image_url).send().await?;
+        if !resp.status().is_success() {
+            return Err(Error::from_response(&resp));
+        }
 
         #[derive(Serialize)]
         struct CreateImage {
@@ -132,26 +197,15 @@ impl crate::Client {
         }
 
         let resp = self
-            .request(reqwest::Method::POST, "api/v1/json/images")
-            .json(&CreateImage {
-                url: image_url,
-                image: im,
-            })
-            .send()
+            .send(
+                self.request(reqwest::Method::POST, "api/v1/json/images")
+                    .json(&CreateImage {
+                        url: image_url,
+                        image: im,
+                    }),
+            )
             .await?;
-
-        match (&resp).error_for_status_ref() {
-            Ok(_) => {
-                let bytes = resp.bytes().await?;
-                log::debug!("body: {}", std::str::from_utf8(&bytes)?);
-                Ok(serde_json::from_slice::<Response>(&bytes)?.image)
-            }
-            Err(why) => {
-                log::debug!("error: {:?}", why);
-                log::debug!("body: {}", resp.text().await?);
-                Err(why.into())
-            }
-        }
+        upload_response(resp).await
     }
 }
 
@@ -206,4 +260,119 @@ mod tests {
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
         cli.image_search("orca", 0).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn image_search_stream() {
+        use futures_util::TryStreamExt;
+
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_images.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/images"),
+                request::query(url_decoded(contains(("page", "1")))),
+            ])
+            .times(1)
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let images: Vec<crate::Image> =
+            cli.image_search_stream("orca").try_collect().await.unwrap();
+        assert_eq!(images.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn search_images() {
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_images.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/images"),
+                request::query(url_decoded(contains(("sf", "score")))),
+                request::query(url_decoded(contains(("per_page", "2")))),
+            ])
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let page = cli
+            .search_images(
+                crate::ImageSearch::new("orca")
+                    .sort_field(crate::ImageSortField::Score)
+                    .per_page(2),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(page.page, 1);
+        assert_eq!(page.page_count(), 2);
+        assert_eq!(page.interactions, Some(vec![]));
+    }
+
+    #[tokio::test]
+    async fn post_image_rejected() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/foo.png"))
+                .respond_with(status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/api/v1/json/images"))
+                .respond_with(status_code(400).body(
+                    r#"{"errors":{"orig_sha512_hash":["has already been taken"],"tag_input":["must contain at least one rating tag"]}}"#,
+                )),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        match cli
+            .post_image(server.url_str("/foo.png"), Default::default())
+            .await
+        {
+            Err(crate::Error::Upload(why)) => {
+                assert!(why.is_duplicate());
+                assert!(why.is_missing_rating());
+                assert!(!why.is_too_large());
+            }
+            result => panic!("wanted an upload error, got {:?}", result),
+        }
+    }
+
+    #[cfg(not(feature = "strict"))]
+    #[test]
+    fn unknown_fields() {
+        let img: crate::Image =
+            serde_json::from_str(r#"{"id": 2336, "sha256_hash": "abc", "tags": ["safe"]}"#)
+                .unwrap();
+        assert_eq!(img.id, 2336);
+        assert_eq!(img.extra["sha256_hash"], "abc");
+        assert_eq!(img.created_at, crate::timestamp::Timestamp::default());
+
+        let again = serde_json::to_value(&img).unwrap();
+        assert_eq!(again["sha256_hash"], "abc");
+    }
+
+    #[cfg(feature = "strict")]
+    #[test]
+    fn unknown_fields() {
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/image_2336.json")).unwrap();
+        let mut img = data["image"].clone();
+        serde_json::from_value::<crate::Image>(img.clone()).unwrap();
+
+        img["sha256_hash"] = "abc".into();
+        assert!(serde_json::from_value::<crate::Image>(img.clone()).is_err());
+
+        img.as_object_mut().unwrap().remove("sha256_hash");
+        img.as_object_mut().unwrap().remove("created_at");
+        assert!(serde_json::from_value::<crate::Image>(img).is_err());
+    }
 }
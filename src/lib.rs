@@ -29,34 +29,76 @@ Set the environment variables `API_USERNAME` and `API_TOKEN` to your
 Furbooru/Derpibooru username and API token respectively. Adding the username
 associated with your bot to each request can help the booru staff when your bot
 does unwanted things like violating rate limits.
+
+Every paginated endpoint also has a `*_stream` method that lazily walks through all
+of its pages. A stream takes the same arguments as the method that fetches one page
+and starts at that page: [`Client::image_search_stream`] starts at the search's
+[`page`](search::ImageSearch::page), and [`Client::forum_thread_stream`] starts at
+its `page` argument, just like [`Client::forum_thread`].
 */
 
+pub mod builder;
 pub mod comment;
+pub mod download;
+pub mod error;
 pub mod filter;
 pub mod firehose;
 pub mod forum;
+pub mod gallery;
 pub mod image;
+pub mod matcher;
+pub mod meta;
+pub mod mirror;
+mod paginate;
 pub mod post;
 pub mod profile;
+pub mod query;
+pub mod ratelimit;
+pub mod retry;
+pub mod reverse;
+pub mod search;
 pub mod tag;
+pub mod timestamp;
 pub mod topic;
+pub mod upload;
 
-pub use anyhow::Result;
-
+pub use builder::ClientBuilder;
 pub use comment::Comment;
-pub use filter::Filter;
+pub use download::Representation;
+pub use error::{Error, Result};
+pub use filter::{Filter, Visibility};
 pub use firehose::{FirehoseAdaptor, Message};
 pub use forum::Forum;
-pub use image::{Image, ImageMeta, Intensities, Representations};
+pub use gallery::Gallery;
+pub use image::{Image, ImageMeta, Intensities, Interaction, Representations};
 pub use post::Post;
 pub use profile::{Award, Link, User};
-pub use tag::Tag;
+pub use ratelimit::{RateLimiter, RequestKind};
+pub use retry::RetryPolicy;
+pub use reverse::ReverseImage;
+pub use search::{
+    CommentSearch, GallerySearch, ImageSearch, ImageSortField, PostSearch, SearchPage,
+    SortDirection, TagSearch,
+};
+pub use tag::{DnpEntry, Tag};
 pub use topic::Topic;
+pub use upload::{Upload, UploadError};
 
+/// Fields of a model that this version of the crate doesn't know about, by name.
+///
+/// The boorus add fields over time. Models keep the ones they don't know about here
+/// instead of dropping them. Fields models know about but that the booru left out
+/// get their default value. The `strict` feature turns both of these into decoding
+/// errors instead, which is useful for noticing API changes in tests.
+pub type Extra = serde_json::Map<String, serde_json::Value>;
+
+#[derive(Clone)]
 pub struct Client {
     pub(crate) cli: reqwest::Client,
-    token: String,
-    api_base: String,
+    token: Option<String>,
+    api_base: url::Url,
+    limiter: Option<RateLimiter>,
+    retry: Option<RetryPolicy>,
 }
 
 static APP_USER_AGENT: &str = concat!(
@@ -68,54 +110,178 @@ static APP_USER_AGENT: &str = concat!(
     " +https://github.com/Xe/furbooru",
 );
 
+static FURBOORU_BASE: &str = "https://furbooru.org/";
+static DERPIBOORU_BASE: &str = "https://derpibooru.org/";
+
 impl Client {
+    /// Start configuring a client. See [`ClientBuilder`] for the available options.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
     /// Create a new client targeting Furbooru.
-    pub fn new<T: Into<String>>(user_agent: T, token: T) -> reqwest::Result<Self> {
-        let cli = reqwest::Client::builder()
-            .user_agent(&format!("{} {}", user_agent.into(), APP_USER_AGENT))
-            .build()?;
-        let cli = Client {
-            cli: cli.into(),
-            token: token.into(),
-            api_base: "https://furbooru.org/".into(),
-        };
-        Ok(cli)
+    pub fn new<T: Into<String>>(user_agent: T, token: T) -> Result<Self> {
+        Self::builder()
+            .furbooru()
+            .user_agent(user_agent)
+            .key(token)
+            .build()
     }
 
     /// Create a new client targeting Derpibooru.
-    pub fn derpi<T: Into<String>>(user_agent: T, token: T) -> reqwest::Result<Self> {
-        let cli = reqwest::Client::builder()
-            .user_agent(&format!("{} {}", user_agent.into(), APP_USER_AGENT))
-            .build()?;
-        let cli = Client {
-            cli: cli.into(),
-            token: token.into(),
-            api_base: "https://derpibooru.org/".into(),
-        };
-        Ok(cli)
+    pub fn derpi<T: Into<String>>(user_agent: T, token: T) -> Result<Self> {
+        Self::builder()
+            .derpibooru()
+            .user_agent(user_agent)
+            .key(token)
+            .build()
+    }
+
+    /// Create a new client targeting Furbooru without an API key. Anonymous clients
+    /// can read public data but calls that need an account fail with
+    /// [`Error::MissingKey`] without touching the network.
+    pub fn anonymous<T: Into<String>>(user_agent: T) -> Result<Self> {
+        Self::builder().furbooru().user_agent(user_agent).build()
     }
 
     /// Create a new client targeting any server you want.
-    pub fn with_baseurl<T: Into<String>>(
-        user_agent: T,
-        token: T,
-        api_base: T,
-    ) -> reqwest::Result<Self> {
-        let cli = reqwest::Client::builder()
-            .user_agent(&format!("{} {}", user_agent.into(), APP_USER_AGENT))
-            .build()?;
-        let cli = Client {
-            cli: cli.into(),
-            token: token.into(),
-            api_base: api_base.into(),
-        };
-        Ok(cli)
+    pub fn with_baseurl<T: Into<String>>(user_agent: T, token: T, api_base: T) -> Result<Self> {
+        Self::builder()
+            .base_url(api_base)
+            .user_agent(user_agent)
+            .key(token)
+            .build()
     }
 
     fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
-        let url = reqwest::Url::parse(&format!("{}{}", self.api_base, path)).unwrap();
-        self.cli.request(method, url).query(&[("key", &self.token)])
+        let req = match self.api_base.join(path) {
+            Ok(url) => self.cli.request(method, url),
+            // reqwest holds on to the bad URL and fails when the request is built.
+            Err(_) => self.cli.request(method, path),
+        };
+        match &self.token {
+            Some(token) => req.query(&[("key", token)]),
+            None => req,
+        }
+    }
+
+    /// Returns true if this client has no API key.
+    pub fn is_anonymous(&self) -> bool {
+        self.token.is_none()
+    }
+
+    /// Fail early for calls that can't work without an API key.
+    fn require_key(&self) -> Result<()> {
+        match self.token {
+            Some(_) => Ok(()),
+            None => Err(Error::MissingKey),
+        }
+    }
+
+    /// Get the rate limiter of this client, if it has one.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.limiter.as_ref()
+    }
+
+    /// Send a request once the rate limiter allows it.
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let req = req.build()?;
+        let kind = RequestKind::of(req.method());
+        self.dispatch(req, kind).await
+    }
+
+    /// Like [`Client::send`], for requests whose method doesn't say what kind of
+    /// request they are, such as searches that have to be `POST`ed.
+    async fn send_as(
+        &self,
+        req: reqwest::RequestBuilder,
+        kind: RequestKind,
+    ) -> Result<reqwest::Response> {
+        self.dispatch(req.build()?, kind).await
+    }
+
+    async fn dispatch(
+        &self,
+        req: reqwest::Request,
+        kind: RequestKind,
+    ) -> Result<reqwest::Response> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire(kind).await;
+        }
+
+        Ok(self.cli.execute(req).await?)
+    }
+
+    /// Send a request and decode its JSON response body, classifying any failure
+    /// into an [`Error`]. `GET` requests are retried according to the client's
+    /// [`RetryPolicy`], if it has one.
+    async fn execute<T: serde::de::DeserializeOwned>(
+        &self,
+        req: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        self.retrying(req.build()?, |req| self.execute_once(req))
+            .await
+    }
+
+    /// Run `once` on `req`, and again on a copy of it each time it fails, as the
+    /// client's [`RetryPolicy`] allows. Only `GET` requests are retried.
+    async fn retrying<T, F, Fut>(&self, req: reqwest::Request, mut once: F) -> Result<T>
+    where
+        F: FnMut(reqwest::Request) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let policy = match &self.retry {
+            Some(policy) if req.method() == reqwest::Method::GET => policy,
+            _ => return once(req).await,
+        };
+
+        let started = std::time::Instant::now();
+        let mut attempt = 1;
+        loop {
+            let this = req.try_clone().expect("GET requests can be cloned");
+            let why = match once(this).await {
+                Err(why)
+                    if attempt < policy.max_attempts_allowed() && policy.is_retryable(&why) =>
+                {
+                    why
+                }
+                result => return result,
+            };
+
+            let delay = match policy.delay(attempt, &why, started.elapsed()) {
+                Some(delay) => delay,
+                None => return Err(why),
+            };
+            log::debug!(
+                "attempt {} at {} failed: {}, retrying in {:?}",
+                attempt,
+                req.url().path(),
+                why,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn execute_once<T: serde::de::DeserializeOwned>(
+        &self,
+        req: reqwest::Request,
+    ) -> Result<T> {
+        let kind = RequestKind::of(req.method());
+        decode(self.dispatch(req, kind).await?).await
+    }
+}
+
+/// Decode the JSON body of a response, classifying an unsuccessful status or a body
+/// that doesn't fit `T` into an [`Error`].
+pub(crate) async fn decode<T: serde::de::DeserializeOwned>(resp: reqwest::Response) -> Result<T> {
+    if !resp.status().is_success() {
+        return Err(Error::from_response(&resp));
     }
+
+    let body = resp.text().await?;
+    serde_json::from_str(&body).map_err(|source| Error::Decode { body, source })
 }
 
 #[cfg(test)]
@@ -141,4 +307,47 @@ mod tests {
 
         assert_eq!(200, resp.status().as_u16());
     }
+
+    #[tokio::test]
+    async fn anonymous() {
+        let _ = pretty_env_logger::try_init();
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/foo"),
+                request::query(url_decoded(not(contains(key("key"))))),
+            ])
+            .respond_with(status_code(200)),
+        );
+
+        let cli = super::Client::builder()
+            .base_url(server.url_str("/"))
+            .build()
+            .unwrap();
+        assert!(cli.is_anonymous());
+        let resp = cli
+            .request(reqwest::Method::GET, "foo")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(200, resp.status().as_u16());
+    }
+
+    #[tokio::test]
+    async fn anonymous_post_image() {
+        let _ = pretty_env_logger::try_init();
+
+        let cli = super::Client::builder()
+            .base_url("http://127.0.0.1:1/")
+            .build()
+            .unwrap();
+        let err = cli
+            .post_image("http://127.0.0.1:1/foo.png".into(), Default::default())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, super::Error::MissingKey));
+    }
 }
@@ -0,0 +1,629 @@
+/*!
+Evaluating parsed [`Query`]s against [`Image`]s without asking the booru.
+
+This lets a firehose bot decide whether a new upload matches a saved search:
+
+```
+use furbooru::{query::Query, Image};
+
+let img = Image {
+    tags: vec!["safe".into(), "orca".into()],
+    score: 120,
+    ..Image::default()
+};
+let q: Query = "safe, orc*, score.gte:100".parse().unwrap();
+assert!(q.matches(&img).unwrap());
+```
+
+Tags are compared case-insensitively against [`Image::tags`], with wildcards. The
+booru resolves tag aliases when it searches, but a local match can't, so search for a
+tag's canonical name rather than one of its aliases.
+
+These fields are evaluated from the image:
+
+| field | compared against |
+|-------|------------------|
+| `id`, `width`, `height`, `score`, `upvotes`, `downvotes`, `faves`, `comment_count`, `tag_count`, `uploader_id` | the field of the same name |
+| `pixels` | `width * height` |
+| `aspect_ratio`, `wilson_score` | the field of the same name |
+| `created_at`, `updated_at`, `first_seen_at` | the field of the same name, see below |
+| `processed`, `thumbnails_generated` | the field of the same name |
+| `sha512_hash`, `orig_sha512_hash`, `uploader`, `source_url`, `mime_type` | the field of the same name, case-insensitively |
+| `format`, `original_format` | [`Image::format`] |
+| `file_name` | [`Image::name`] |
+| `description` | [`Image::description`], matching when the description contains the value |
+
+Date values can be absolute (`2020`, `2020-06`, `2020-06-11`, `2020-06-11T03:43:32Z`)
+or relative (`3 days ago`). Like on the booru, an absolute date covers the whole period
+it names, so `created_at:2020` matches any image uploaded in 2020 and
+`created_at.gt:2020` only matches images uploaded after it.
+
+Only the booru can evaluate these, because an [`Image`] doesn't carry enough data:
+`faved_by`, `faved_by_id`, `my`, `gallery_id`, `source_count`, `size`, `duration`
+and `animated`, as well as fuzzy terms (`~`). Matching a query that uses them fails
+with [`EvalErrorKind::ServerOnly`]. Boosts only change the order of search results,
+so they are ignored.
+
+A term that can't be evaluated never quietly fails to match. [`Query::parse`] already
+rejects most bad terms, but queries built by hand or with
+[`Query::parse_with_fields`] can hold anything: fields that aren't image search fields
+fail with [`EvalErrorKind::UnknownField`], range operators on fields without ranges,
+like `uploader.gt:bob`, fail with [`EvalErrorKind::InvalidRange`], and values that
+don't fit their field, like `score.gte:abc`, fail with
+[`EvalErrorKind::InvalidNumber`], [`EvalErrorKind::InvalidBoolean`] or
+[`EvalErrorKind::InvalidDate`].
+*/
+
+use crate::{
+    query::{FieldKind, Pattern, PatternPart, Query, RangeOp, Span, Term, TermKind, IMAGE_FIELDS},
+    timestamp::{self, Timestamp},
+    Image,
+};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// What went wrong while evaluating a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalErrorKind {
+    /// The term can only be evaluated by the booru.
+    ServerOnly(String),
+    /// The field isn't one of the image search fields.
+    UnknownField(String),
+    /// A range operator was used with a field that doesn't support ranges. The
+    /// parser rejects these with [`ParseErrorKind::InvalidRange`](crate::query::ParseErrorKind::InvalidRange),
+    /// but hand-built queries can still hold them.
+    InvalidRange(String),
+    /// A numeric field was given something that isn't a number.
+    InvalidNumber(String),
+    /// A boolean field was given something other than `true` or `false`.
+    InvalidBoolean(String),
+    /// A date field was given something that isn't a date.
+    InvalidDate(String),
+}
+
+/// An error found while evaluating a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalError {
+    pub kind: EvalErrorKind,
+    /// The span of the offending term.
+    pub span: Span,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            EvalErrorKind::ServerOnly(what) => {
+                write!(f, "{} can only be searched by the booru", what)?
+            }
+            EvalErrorKind::UnknownField(name) => write!(f, "unknown field {}", name)?,
+            EvalErrorKind::InvalidRange(name) => {
+                write!(f, "field {} can't be used with a range operator", name)?
+            }
+            EvalErrorKind::InvalidNumber(value) => write!(f, "{:?} is not a number", value)?,
+            EvalErrorKind::InvalidBoolean(value) => write!(f, "{:?} is not true or false", value)?,
+            EvalErrorKind::InvalidDate(value) => write!(f, "{:?} is not a date", value)?,
+        };
+        write!(f, " at {}..{}", self.span.start, self.span.end)
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl Query {
+    /// Check whether `img` matches this query. See the [module documentation](self)
+    /// for what can be evaluated locally.
+    ///
+    /// Every term is checked before anything is evaluated, so a query that can't be
+    /// evaluated locally always fails, no matter which image it is given.
+    pub fn matches(&self, img: &Image) -> Result<bool, EvalError> {
+        self.matches_at(img, unix_now())
+    }
+
+    pub(crate) fn matches_at(&self, img: &Image, now: i64) -> Result<bool, EvalError> {
+        for term in self.terms() {
+            check(term, now)?;
+        }
+        Ok(self.eval(img, now))
+    }
+
+    fn eval(&self, img: &Image, now: i64) -> bool {
+        match self {
+            Query::Term(term) => eval_term(term, img, now),
+            Query::Not(q) => !q.eval(img, now),
+            Query::And(qs) => qs.iter().all(|q| q.eval(img, now)),
+            Query::Or(qs) => qs.iter().any(|q| q.eval(img, now)),
+        }
+    }
+}
+
+impl Pattern {
+    /// Check whether `text` matches this pattern, wildcards included.
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        glob(self.parts(), &text)
+    }
+
+    /// Like [`Pattern::matches`], ignoring case.
+    pub fn matches_ignore_case(&self, text: &str) -> bool {
+        glob_ignore_case(self.parts(), text)
+    }
+}
+
+fn glob_ignore_case(parts: &[PatternPart], text: &str) -> bool {
+    let parts: Vec<PatternPart> = parts
+        .iter()
+        .map(|part| match part {
+            PatternPart::Literal(lit) => PatternPart::Literal(lit.to_lowercase()),
+            part => part.clone(),
+        })
+        .collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    glob(&parts, &text)
+}
+
+fn glob(parts: &[PatternPart], text: &[char]) -> bool {
+    match parts.split_first() {
+        None => text.is_empty(),
+        Some((PatternPart::Literal(lit), rest)) => {
+            let lit: Vec<char> = lit.chars().collect();
+            text.starts_with(&lit) && glob(rest, &text[lit.len()..])
+        }
+        Some((PatternPart::AnyChar, rest)) => !text.is_empty() && glob(rest, &text[1..]),
+        Some((PatternPart::AnyString, rest)) => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+    }
+}
+
+fn server_only(what: String, term: &Term) -> Result<(), EvalError> {
+    Err(EvalError {
+        kind: EvalErrorKind::ServerOnly(what),
+        span: term.span,
+    })
+}
+
+fn check(term: &Term, now: i64) -> Result<(), EvalError> {
+    if term.fuzz.is_some() {
+        return server_only("fuzzy matching".into(), term);
+    }
+
+    let (name, op, value) = match &term.kind {
+        TermKind::Tag(_) => return Ok(()),
+        TermKind::Field { name, op, value } => (name.as_str(), *op, value),
+    };
+    if let "faved_by" | "faved_by_id" | "my" | "gallery_id" | "source_count" | "size" | "duration"
+    | "animated" = name
+    {
+        return server_only(format!("field {}", name), term);
+    }
+
+    let invalid = |kind| {
+        Err(EvalError {
+            kind,
+            span: term.span,
+        })
+    };
+    let kind = match IMAGE_FIELDS.iter().find(|field| field.name == name) {
+        Some(field) => field.kind,
+        None => return invalid(EvalErrorKind::UnknownField(name.to_string())),
+    };
+    if op != RangeOp::Eq && !kind.is_ranged() {
+        return invalid(EvalErrorKind::InvalidRange(name.to_string()));
+    }
+    let literal = value.as_literal();
+    match kind {
+        FieldKind::Integer if literal.as_deref().and_then(parse_int).is_none() => {
+            invalid(EvalErrorKind::InvalidNumber(value.to_string()))
+        }
+        FieldKind::Float if literal.as_deref().and_then(parse_float).is_none() => {
+            invalid(EvalErrorKind::InvalidNumber(value.to_string()))
+        }
+        FieldKind::Boolean if literal.as_deref().and_then(parse_bool).is_none() => {
+            invalid(EvalErrorKind::InvalidBoolean(value.to_string()))
+        }
+        FieldKind::Date
+            if literal
+                .as_deref()
+                .and_then(|v| parse_date(v, now))
+                .is_none() =>
+        {
+            invalid(EvalErrorKind::InvalidDate(value.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn parse_int(value: &str) -> Option<i64> {
+    value.trim().parse().ok()
+}
+
+fn parse_float(value: &str) -> Option<f64> {
+    value.trim().parse().ok()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn eval_term(term: &Term, img: &Image, now: i64) -> bool {
+    let (name, op, value) = match &term.kind {
+        TermKind::Tag(pattern) => {
+            return img.tags.iter().any(|tag| pattern.matches_ignore_case(tag))
+        }
+        TermKind::Field { name, op, value } => (name.as_str(), *op, value),
+    };
+
+    // `check` has made sure the value fits the field.
+    let int = |field: Option<i64>| match (field, value.as_literal().as_deref().and_then(parse_int))
+    {
+        (Some(field), Some(value)) => compare(op, field, value),
+        _ => false,
+    };
+    let float = |field: f64| match value.as_literal().as_deref().and_then(parse_float) {
+        Some(value) => compare(op, field, value),
+        None => false,
+    };
+    let literal = |field: Option<&str>| field.is_some_and(|field| value.matches_ignore_case(field));
+
+    match name {
+        "id" => int(Some(img.id)),
+        "width" => int(Some(img.width)),
+        "height" => int(Some(img.height)),
+        "score" => int(Some(img.score)),
+        "upvotes" => int(Some(img.upvotes)),
+        "downvotes" => int(Some(img.downvotes)),
+        "faves" => int(Some(img.faves)),
+        "comment_count" => int(Some(img.comment_count)),
+        "tag_count" => int(Some(img.tag_count)),
+        "uploader_id" => int(img.uploader_id),
+        "pixels" => int(Some(img.width * img.height)),
+        "aspect_ratio" => float(img.aspect_ratio),
+        "wilson_score" => float(img.wilson_score),
+        "created_at" => eval_date(op, value, &img.created_at, now),
+        "updated_at" => eval_date(op, value, &img.updated_at, now),
+        "first_seen_at" => eval_date(op, value, &img.first_seen_at, now),
+        "processed" => eval_bool(value, img.processed),
+        "thumbnails_generated" => eval_bool(value, img.thumbnails_generated),
+        "sha512_hash" => literal(Some(&img.sha512_hash)),
+        "orig_sha512_hash" => literal(Some(&img.orig_sha512_hash)),
+        "uploader" => literal(img.uploader.as_deref()),
+        "source_url" => literal(img.source_url.as_deref()),
+        "mime_type" => literal(Some(&img.mime_type)),
+        "format" | "original_format" => literal(Some(&img.format)),
+        "file_name" => literal(Some(&img.name)),
+        "description" => {
+            let mut parts = vec![PatternPart::AnyString];
+            parts.extend_from_slice(value.parts());
+            parts.push(PatternPart::AnyString);
+            glob_ignore_case(&parts, &img.description)
+        }
+        _ => unreachable!("{} was let through by check", name),
+    }
+}
+
+fn compare<T: PartialOrd>(op: RangeOp, field: T, value: T) -> bool {
+    match op {
+        RangeOp::Eq => field == value,
+        RangeOp::Gt => field > value,
+        RangeOp::Gte => field >= value,
+        RangeOp::Lt => field < value,
+        RangeOp::Lte => field <= value,
+    }
+}
+
+fn eval_bool(value: &Pattern, field: bool) -> bool {
+    value.as_literal().as_deref().and_then(parse_bool) == Some(field)
+}
+
+fn eval_date(op: RangeOp, value: &Pattern, field: &Timestamp, now: i64) -> bool {
+    let (lower, upper) = match value.as_literal().and_then(|v| parse_date(&v, now)) {
+        Some(range) => range,
+        None => return false,
+    };
+    let time = match timestamp::unix(field) {
+        Some(time) => time,
+        None => return false,
+    };
+
+    match op {
+        RangeOp::Eq => lower <= time && time < upper,
+        RangeOp::Gt => time >= upper,
+        RangeOp::Gte => time >= lower,
+        RangeOp::Lt => time < lower,
+        RangeOp::Lte => time < upper,
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Parse a date into the range of unix timestamps it covers, as `[lower, upper)`.
+///
+/// This understands the ISO 8601 subset Philomena accepts, down to any precision, and
+/// relative dates like `3 days ago`. Dates without a UTC offset are taken to be UTC.
+pub(crate) fn parse_date(text: &str, now: i64) -> Option<(i64, i64)> {
+    let text = text.trim();
+    if let Some(rest) = text.strip_suffix("ago") {
+        return parse_relative(rest.trim(), now);
+    }
+
+    let (text, offset) = split_offset(text)?;
+    let bytes = text.as_bytes();
+
+    // (position of the number, its length, the separator before it)
+    let layout: &[(usize, usize, u8)] = &[
+        (0, 4, b' '),
+        (5, 2, b'-'),
+        (8, 2, b'-'),
+        (11, 2, b'T'),
+        (14, 2, b':'),
+        (17, 2, b':'),
+    ];
+    let mut parts = [1970i64, 1, 1, 0, 0, 0];
+    let mut precision = 0;
+    for (i, (pos, len, sep)) in layout.iter().enumerate() {
+        if bytes.len() <= *pos {
+            break;
+        }
+        if i != 0 {
+            let found = bytes[pos - 1];
+            let ok = found == *sep || (*sep == b'T' && found == b' ');
+            if !ok {
+                return None;
+            }
+        }
+        let end = pos + len;
+        let digits = text.get(*pos..end)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        parts[i] = digits.parse().ok()?;
+        precision = i + 1;
+    }
+
+    // allow fractional seconds
+    let consumed = match precision {
+        0 => return None,
+        6 => match text.get(19..) {
+            Some("") => 19,
+            Some(frac)
+                if frac.starts_with('.') && frac[1..].bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                text.len()
+            }
+            _ => return None,
+        },
+        n => layout[n - 1].0 + layout[n - 1].1,
+    };
+    if consumed != text.len() {
+        return None;
+    }
+
+    let [year, month, day, hour, minute, second] = parts;
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let start = |y: i64, mo: i64, d: i64, h: i64, mi: i64, s: i64| {
+        days_from_civil(y, mo, d) * 86400 + h * 3600 + mi * 60 + s - offset
+    };
+    let lower = start(year, month, day, hour, minute, second);
+    let upper = match precision {
+        1 => start(year + 1, 1, 1, 0, 0, 0),
+        2 if month == 12 => start(year + 1, 1, 1, 0, 0, 0),
+        2 => start(year, month + 1, 1, 0, 0, 0),
+        3 => lower + 86400,
+        4 => lower + 3600,
+        5 => lower + 60,
+        _ => lower + 1,
+    };
+    Some((lower, upper))
+}
+
+/// Split a trailing `Z` or `+HH:MM` off a date, returning the offset in seconds.
+fn split_offset(text: &str) -> Option<(&str, i64)> {
+    if let Some(text) = text.strip_suffix('Z') {
+        return Some((text, 0));
+    }
+    if text.len() > 6 && text.is_char_boundary(text.len() - 6) {
+        let (date, offset) = text.split_at(text.len() - 6);
+        let sign = match offset.as_bytes()[0] {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return Some((text, 0)),
+        };
+        if date.len() < 13 || offset.as_bytes()[3] != b':' {
+            return Some((text, 0));
+        }
+        let hours: i64 = offset[1..3].parse().ok()?;
+        let minutes: i64 = offset[4..6].parse().ok()?;
+        return Some((date, sign * (hours * 3600 + minutes * 60)));
+    }
+    Some((text, 0))
+}
+
+fn parse_relative(text: &str, now: i64) -> Option<(i64, i64)> {
+    let mut words = text.split_whitespace();
+    let count: i64 = words.next()?.parse().ok()?;
+    let unit = words.next()?;
+    if words.next().is_some() {
+        return None;
+    }
+
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 3600,
+        "day" => 86400,
+        "week" => 7 * 86400,
+        "month" => 30 * 86400,
+        "year" => 365 * 86400,
+        _ => return None,
+    };
+    let lower = now.checked_sub(count.checked_mul(seconds)?)?;
+    Some((lower, lower + seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image {
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/image_2336.json")).unwrap();
+        serde_json::from_value(data["image"].clone()).unwrap()
+    }
+
+    fn matches(q: &str) -> bool {
+        // 2020-07-01T00:00:00Z
+        Query::parse(q)
+            .unwrap()
+            .matches_at(&image(), 1593561600)
+            .unwrap()
+    }
+
+    #[test]
+    fn tags() {
+        assert!(matches("safe"));
+        assert!(matches("Pink Eyes"));
+        assert!(matches("artist:*"));
+        assert!(matches("oc:cad?y"));
+        assert!(!matches("explicit"));
+        assert!(matches("safe, orca || explicit"));
+        assert!(!matches("safe, -orca"));
+    }
+
+    #[test]
+    fn fields() {
+        assert!(matches("width:1280, height.lt:1000, pixels.gte:1000000"));
+        assert!(matches("aspect_ratio.gt:1.5, wilson_score.lt:0.2"));
+        assert!(matches("uploader:alicia, uploader_id:237"));
+        assert!(matches("format:jpg, mime_type:image/*, processed:true"));
+        assert!(matches("source_url:*twitter.com*"));
+        assert!(matches("sha512_hash:935a017a*"));
+        assert!(!matches("score.gt:1"));
+        assert!(!matches("thumbnails_generated:false"));
+    }
+
+    #[test]
+    fn dates() {
+        assert!(matches("created_at:2020"));
+        assert!(matches("created_at:2020-06-11"));
+        assert!(!matches("created_at.gt:2020-06"));
+        assert!(matches("created_at.lte:2020-06"));
+        assert!(matches("created_at.lt:2020-06-11T03:43:33Z"));
+        assert!(matches("created_at.gt:2020-06-11T05:00:00+02:00"));
+        assert!(matches("created_at.gte:4 weeks ago"));
+        assert!(!matches("created_at.gte:1 week ago"));
+    }
+
+    #[test]
+    fn errors() {
+        let q = Query::parse("safe, my:faves").unwrap();
+        assert_eq!(
+            q.matches(&image()),
+            Err(EvalError {
+                kind: EvalErrorKind::ServerOnly("field my".into()),
+                span: Span { start: 6, end: 14 },
+            })
+        );
+
+        let q = Query::parse("created_at:yesterday").unwrap();
+        assert!(matches!(
+            q.matches(&image()),
+            Err(EvalError {
+                kind: EvalErrorKind::InvalidDate(_),
+                ..
+            })
+        ));
+
+        // The parser rejects these, but queries can also be built by hand.
+        let kind = |name: &str, op: RangeOp, value: &str| {
+            Query::field(name, op, value)
+                .matches(&image())
+                .unwrap_err()
+                .kind
+        };
+        assert_eq!(
+            kind("score", RangeOp::Gte, "abc"),
+            EvalErrorKind::InvalidNumber("abc".into())
+        );
+        assert_eq!(
+            kind("aspect_ratio", RangeOp::Gte, "wide"),
+            EvalErrorKind::InvalidNumber("wide".into())
+        );
+        assert_eq!(
+            kind("processed", RangeOp::Eq, "yes"),
+            EvalErrorKind::InvalidBoolean("yes".into())
+        );
+        assert_eq!(
+            kind("favorite_color", RangeOp::Eq, "pink"),
+            EvalErrorKind::UnknownField("favorite_color".into())
+        );
+        assert_eq!(
+            kind("uploader", RangeOp::Gt, "bob"),
+            EvalErrorKind::InvalidRange("uploader".into())
+        );
+        assert_eq!(
+            kind("processed", RangeOp::Lt, "true"),
+            EvalErrorKind::InvalidRange("processed".into())
+        );
+
+        // the count comes straight from the query, so it can be anything
+        let q = Query::parse("created_at.gte:9999999999999999 years ago").unwrap();
+        assert!(matches!(
+            q.matches(&image()),
+            Err(EvalError {
+                kind: EvalErrorKind::InvalidDate(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn every_field() {
+        // Every field that passes `check` has to be evaluated, not fall through.
+        for field in IMAGE_FIELDS {
+            let value = match field.kind {
+                FieldKind::Integer => "1",
+                FieldKind::Float => "1.5",
+                FieldKind::Date => "2020",
+                FieldKind::Boolean => "true",
+                FieldKind::Literal | FieldKind::Text => "orca",
+            };
+            let q = Query::field(field.name, RangeOp::Eq, value);
+            match q.matches(&image()) {
+                Ok(_)
+                | Err(EvalError {
+                    kind: EvalErrorKind::ServerOnly(_),
+                    ..
+                }) => {}
+                Err(why) => panic!("{}: {}", field.name, why),
+            }
+        }
+    }
+}
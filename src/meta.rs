@@ -0,0 +1,313 @@
+/*!
+Checking [`ImageMeta`] before uploading it.
+
+```
+use furbooru::{meta::TagRules, ImageMeta};
+
+let meta = ImageMeta {
+    tag_input: "Safe, orca,  artist:atryl, oc".into(),
+    source_url: "https://twitter.com/atryl".into(),
+    ..ImageMeta::default()
+};
+let tags = meta.validate(&TagRules::new()).unwrap();
+assert_eq!(tags, vec!["safe", "orca", "artist:atryl", "oc"]);
+```
+*/
+
+use crate::ImageMeta;
+use std::fmt;
+
+/// The tagging rules a booru enforces on uploads.
+///
+/// [`TagRules::new`] has the rules Philomena ships with, which Furbooru and
+/// Derpibooru both use. Change them to match other boorus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagRules {
+    rating_groups: Vec<Vec<String>>,
+    exclusive_ratings: Vec<String>,
+    min_tags: usize,
+}
+
+impl Default for TagRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TagRules {
+    /// Philomena's rules: at least three tags and at least one rating. The rating
+    /// groups are `safe`, the sexual ratings (`suggestive`, `questionable`,
+    /// `explicit`), the horror ratings (`semi-grimdark`, `grimdark`) and the gross
+    /// rating (`grotesque`). An image can have at most one rating from each group, and
+    /// `safe` can't be combined with any other rating.
+    pub fn new() -> Self {
+        Self {
+            rating_groups: Vec::new(),
+            exclusive_ratings: Vec::new(),
+            min_tags: 3,
+        }
+        .rating_groups(vec![
+            vec!["safe"],
+            vec!["suggestive", "questionable", "explicit"],
+            vec!["semi-grimdark", "grimdark"],
+            vec!["grotesque"],
+        ])
+        .exclusive_ratings(["safe"])
+    }
+
+    /// Set the rating tags, in groups. An image needs at least one rating and can
+    /// have at most one rating from each group.
+    pub fn rating_groups<G, I, S>(mut self, groups: G) -> Self
+    where
+        G: IntoIterator<Item = I>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.rating_groups = groups
+            .into_iter()
+            .map(|group| group.into_iter().map(|r| normalize(r.as_ref())).collect())
+            .collect();
+        self
+    }
+
+    /// Set the ratings that can't be combined with any other rating.
+    pub fn exclusive_ratings<I, S>(mut self, ratings: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.exclusive_ratings = ratings.into_iter().map(|r| normalize(r.as_ref())).collect();
+        self
+    }
+
+    /// Set how many tags an image needs.
+    pub fn min_tags(mut self, min_tags: usize) -> Self {
+        self.min_tags = min_tags;
+        self
+    }
+}
+
+/// Something wrong with an [`ImageMeta`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetaProblem {
+    /// None of the rating tags are there.
+    NoRating,
+    /// Rating tags that can't be used together are there.
+    ConflictingRatings(Vec<String>),
+    /// The same tag is there more than once, maybe with different case. This holds
+    /// each spelling that was used.
+    DuplicateTag(Vec<String>),
+    /// There are fewer tags than the booru needs.
+    TooFewTags { found: usize, min: usize },
+    /// The source URL isn't an absolute http or https URL.
+    InvalidSourceUrl(String),
+}
+
+impl fmt::Display for MetaProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetaProblem::NoRating => f.write_str("no rating tag"),
+            MetaProblem::ConflictingRatings(ratings) => {
+                write!(f, "conflicting rating tags: {}", ratings.join(", "))
+            }
+            MetaProblem::DuplicateTag(spellings) => {
+                write!(f, "duplicate tag: {}", spellings.join(", "))
+            }
+            MetaProblem::TooFewTags { found, min } => {
+                write!(f, "{} tags, at least {} are needed", found, min)
+            }
+            MetaProblem::InvalidSourceUrl(url) => write!(f, "invalid source url: {}", url),
+        }
+    }
+}
+
+/// Everything wrong with an [`ImageMeta`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaError {
+    pub problems: Vec<MetaProblem>,
+}
+
+impl fmt::Display for MetaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, problem) in self.problems.iter().enumerate() {
+            if i != 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MetaError {}
+
+/// Lowercase a tag and squash its whitespace, like the booru does.
+fn normalize(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl ImageMeta {
+    /// Split `tag_input` into normalized tags, in order, without duplicates.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tag_input.split(',').map(normalize) {
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    /// Check this against a booru's tagging rules, returning the normalized tags if
+    /// nothing is wrong. Every problem found is reported, not just the first.
+    pub fn validate(&self, rules: &TagRules) -> Result<Vec<String>, MetaError> {
+        let mut problems = Vec::new();
+
+        let mut spellings: Vec<(String, Vec<String>)> = Vec::new();
+        for written in self.tag_input.split(',').map(str::trim) {
+            let tag = normalize(written);
+            if tag.is_empty() {
+                continue;
+            }
+            match spellings.iter_mut().find(|(t, _)| *t == tag) {
+                Some((_, seen)) => seen.push(written.to_string()),
+                None => spellings.push((tag, vec![written.to_string()])),
+            }
+        }
+        for (_, seen) in &spellings {
+            if seen.len() > 1 {
+                problems.push(MetaProblem::DuplicateTag(seen.clone()));
+            }
+        }
+
+        let tags: Vec<String> = spellings.into_iter().map(|(tag, _)| tag).collect();
+        let of = |is_rating: &dyn Fn(&String) -> bool| -> Vec<String> {
+            tags.iter().filter(|tag| is_rating(tag)).cloned().collect()
+        };
+        let all = of(&|tag| rules.rating_groups.iter().any(|group| group.contains(tag)));
+        if all.is_empty() {
+            problems.push(MetaProblem::NoRating);
+        } else if all.len() > 1 && all.iter().any(|r| rules.exclusive_ratings.contains(r)) {
+            problems.push(MetaProblem::ConflictingRatings(all));
+        } else {
+            for group in &rules.rating_groups {
+                let found = of(&|tag| group.contains(tag));
+                if found.len() > 1 {
+                    problems.push(MetaProblem::ConflictingRatings(found));
+                }
+            }
+        }
+
+        if tags.len() < rules.min_tags {
+            problems.push(MetaProblem::TooFewTags {
+                found: tags.len(),
+                min: rules.min_tags,
+            });
+        }
+
+        if !self.source_url.is_empty() {
+            let valid = url::Url::parse(&self.source_url)
+                .map(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+                .unwrap_or(false);
+            if !valid {
+                problems.push(MetaProblem::InvalidSourceUrl(self.source_url.clone()));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(tags)
+        } else {
+            Err(MetaError { problems })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(tags: &str, source_url: &str) -> Result<Vec<String>, MetaError> {
+        ImageMeta {
+            tag_input: tags.into(),
+            source_url: source_url.into(),
+            ..ImageMeta::default()
+        }
+        .validate(&TagRules::new())
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            validate("safe,  Pink   Eyes, orca,", "").unwrap(),
+            vec!["safe", "pink eyes", "orca"]
+        );
+        assert!(validate("grimdark, explicit, orca", "http://example.com").is_ok());
+    }
+
+    #[test]
+    fn problems() {
+        let why = validate("Safe, explicit, safe", "ftp://example.com").unwrap_err();
+        assert_eq!(
+            why.problems,
+            vec![
+                MetaProblem::DuplicateTag(vec!["Safe".into(), "safe".into()]),
+                MetaProblem::ConflictingRatings(vec!["safe".into(), "explicit".into()]),
+                MetaProblem::TooFewTags { found: 2, min: 3 },
+                MetaProblem::InvalidSourceUrl("ftp://example.com".into()),
+            ]
+        );
+
+        let why = validate("orca, oc, water", "").unwrap_err();
+        assert_eq!(why.problems, vec![MetaProblem::NoRating]);
+    }
+
+    #[test]
+    fn grim_ratings() {
+        assert!(validate("explicit, grimdark, orca", "").is_ok());
+        assert!(validate("grotesque, orca, oc", "").is_ok());
+        assert!(validate("explicit, grimdark, grotesque, orca", "").is_ok());
+
+        let why = validate("safe, grimdark, orca", "").unwrap_err();
+        assert_eq!(
+            why.problems,
+            vec![MetaProblem::ConflictingRatings(vec![
+                "safe".into(),
+                "grimdark".into()
+            ])]
+        );
+
+        let why = validate("safe, grotesque, orca", "").unwrap_err();
+        assert_eq!(
+            why.problems,
+            vec![MetaProblem::ConflictingRatings(vec![
+                "safe".into(),
+                "grotesque".into()
+            ])]
+        );
+
+        let why = validate("semi-grimdark, grimdark, orca", "").unwrap_err();
+        assert_eq!(
+            why.problems,
+            vec![MetaProblem::ConflictingRatings(vec![
+                "semi-grimdark".into(),
+                "grimdark".into()
+            ])]
+        );
+    }
+
+    #[test]
+    fn rules() {
+        let rules = TagRules::new()
+            .rating_groups([["SFW", "NSFW"]])
+            .exclusive_ratings(Vec::<String>::new())
+            .min_tags(1);
+        let meta = ImageMeta {
+            tag_input: "sfw".into(),
+            ..ImageMeta::default()
+        };
+        assert_eq!(meta.validate(&rules).unwrap(), vec!["sfw"]);
+    }
+}
@@ -0,0 +1,346 @@
+/*!
+Archiving every image that matches a search to a local directory.
+
+```no_run
+# async fn run(cli: furbooru::Client) -> furbooru::Result<()> {
+use furbooru::mirror::Mirror;
+
+let mirror = Mirror::new("./archive/starliiite")
+    .concurrency(4)
+    .layout(|img| format!("{:03}/{}.{}", img.id / 1000, img.id, img.format).into());
+let report = cli.mirror("artist:starliiite", &mirror).await?;
+println!("downloaded {} images", report.downloaded);
+# Ok(())
+# }
+```
+
+Each image is saved next to a JSON sidecar holding its full [`Image`] record, at the
+image's path with `.json` added to the end. Images whose file is already there with
+the right SHA-512 hash are skipped. The IDs of finished images are written to a state
+file as the mirror runs, so running the same mirror again after an interruption
+skips them without touching the network.
+*/
+
+use crate::{download::Representation, search::ImageSearch, Error, Image, Result};
+use futures_util::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+/// The name of the state file in the mirror directory, unless another one is set.
+pub const DEFAULT_STATE_FILE: &str = ".furbooru-mirror.json";
+
+type Layout = dyn Fn(&Image) -> PathBuf + Send + Sync;
+
+/// Where and how to mirror images. See the [module documentation](self).
+#[derive(Clone)]
+pub struct Mirror {
+    root: PathBuf,
+    concurrency: usize,
+    layout: Arc<Layout>,
+    state_file: Option<PathBuf>,
+}
+
+impl fmt::Debug for Mirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mirror")
+            .field("root", &self.root)
+            .field("concurrency", &self.concurrency)
+            .field("state_file", &self.state_file)
+            .finish()
+    }
+}
+
+impl Mirror {
+    /// Mirror images into `root`, two at a time, saving each one as `{id}.{format}`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            concurrency: 2,
+            layout: Arc::new(|img| format!("{}.{}", img.id, img.format).into()),
+            state_file: None,
+        }
+    }
+
+    /// Set how many images are downloaded at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set where each image is saved, relative to the mirror directory. Paths that
+    /// try to leave the mirror directory are rejected with [`Error::Layout`].
+    pub fn layout<F>(mut self, layout: F) -> Self
+    where
+        F: Fn(&Image) -> PathBuf + Send + Sync + 'static,
+    {
+        self.layout = Arc::new(layout);
+        self
+    }
+
+    /// Keep the state file here instead of at [`DEFAULT_STATE_FILE`] in the mirror
+    /// directory.
+    pub fn state_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.state_file
+            .clone()
+            .unwrap_or_else(|| self.root.join(DEFAULT_STATE_FILE))
+    }
+
+    fn path_of(&self, img: &Image) -> Result<PathBuf> {
+        let rel = (self.layout)(img);
+        let escapes = rel.components().any(|c| {
+            !matches!(
+                c,
+                std::path::Component::Normal(_) | std::path::Component::CurDir
+            )
+        });
+        if escapes || rel.as_os_str().is_empty() {
+            return Err(Error::Layout(rel));
+        }
+        Ok(self.root.join(rel))
+    }
+}
+
+/// What a mirror run did.
+#[derive(Debug, Default)]
+pub struct MirrorReport {
+    /// How many images were downloaded.
+    pub downloaded: u64,
+    /// How many images were already mirrored.
+    pub skipped: u64,
+    /// The images that could not be mirrored, by ID. They are tried again the next
+    /// time the mirror runs.
+    pub failed: Vec<(i64, Error)>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    done: BTreeSet<i64>,
+}
+
+enum Outcome {
+    Downloaded,
+    Skipped,
+}
+
+impl crate::Client {
+    /// Download every image matching `search` into a local directory. See the
+    /// [`mirror`](crate::mirror) module for the details.
+    ///
+    /// A failed search, an invalid state file ([`Error::State`]) or a failed state
+    /// file write stops the run. Images that fail to download are listed in the
+    /// report instead.
+    pub async fn mirror<S: Into<ImageSearch>>(
+        &self,
+        search: S,
+        mirror: &Mirror,
+    ) -> Result<MirrorReport> {
+        tokio::fs::create_dir_all(&mirror.root)
+            .await
+            .map_err(Error::Io)?;
+        let state_path = mirror.state_path();
+        let state = match tokio::fs::read(&state_path).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(|source| Error::State {
+                path: state_path.clone(),
+                source,
+            })?,
+            Err(why) if why.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(why) => return Err(Error::Io(why)),
+        };
+        let state = &Mutex::new(state);
+        let state_path = &state_path;
+
+        let results = self
+            .image_search_stream(search)
+            .map_ok(|img| async move {
+                let id = img.id;
+                if state.lock().await.done.contains(&id) {
+                    return Ok((id, Ok(Outcome::Skipped)));
+                }
+
+                let outcome = self.mirror_image(&img, mirror).await;
+                if outcome.is_ok() {
+                    let mut state = state.lock().await;
+                    state.done.insert(id);
+                    save_state(state_path, &state).await?;
+                }
+                Ok((id, outcome))
+            })
+            .try_buffer_unordered(mirror.concurrency);
+        futures_util::pin_mut!(results);
+
+        let mut report = MirrorReport::default();
+        while let Some(result) = results.next().await {
+            match result? {
+                (_, Ok(Outcome::Downloaded)) => report.downloaded += 1,
+                (_, Ok(Outcome::Skipped)) => report.skipped += 1,
+                (id, Err(why)) => {
+                    log::debug!("can't mirror image {}: {}", id, why);
+                    report.failed.push((id, why));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn mirror_image(&self, img: &Image, mirror: &Mirror) -> Result<Outcome> {
+        let path = mirror.path_of(img)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await.map_err(Error::Io)?;
+        }
+
+        let sidecar = with_suffix(&path, ".json");
+        let meta = serde_json::to_vec_pretty(img).expect("images can be serialized");
+
+        if has_hash(&path, img).await? {
+            tokio::fs::write(&sidecar, meta).await.map_err(Error::Io)?;
+            return Ok(Outcome::Skipped);
+        }
+
+        let part = with_suffix(&path, ".part");
+        let mut file = tokio::fs::File::create(&part).await.map_err(Error::Io)?;
+        if let Err(why) = self
+            .download_image(img, Representation::Full, &mut file)
+            .await
+        {
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(why);
+        }
+        drop(file);
+
+        tokio::fs::rename(&part, &path).await.map_err(Error::Io)?;
+        tokio::fs::write(&sidecar, meta).await.map_err(Error::Io)?;
+        Ok(Outcome::Downloaded)
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// Returns true if `path` already holds the image's file.
+async fn has_hash(path: &Path, img: &Image) -> Result<bool> {
+    let data = match tokio::fs::read(path).await {
+        Ok(data) => data,
+        Err(why) if why.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(why) => return Err(Error::Io(why)),
+    };
+
+    let hash = hex::encode(Sha512::digest(&data));
+    Ok([&img.sha512_hash, &img.orig_sha512_hash]
+        .iter()
+        .any(|expected| expected.eq_ignore_ascii_case(&hash)))
+}
+
+/// Write the state file atomically, so an interrupted run never leaves it half
+/// written.
+async fn save_state(path: &Path, state: &State) -> Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    let data = serde_json::to_vec(state).expect("mirror state can be serialized");
+    tokio::fs::write(&tmp, data).await.map_err(Error::Io)?;
+    tokio::fs::rename(&tmp, path).await.map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mirror;
+    use crate::Error;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use sha2::{Digest, Sha512};
+
+    #[tokio::test]
+    async fn mirror() {
+        let _ = pretty_env_logger::try_init();
+        let mut data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_images.json")).unwrap();
+        let server = Server::run();
+        for img in data["images"].as_array_mut().unwrap() {
+            let id = img["id"].as_i64().unwrap();
+            let body = format!("image {}", id);
+            let path = format!("/img/view/{}.png", id);
+            img["representations"]["full"] = server.url_str(&path).into();
+            img["sha512_hash"] = hex::encode(Sha512::digest(&body)).into();
+            server.expect(
+                Expectation::matching(request::method_path("GET", path))
+                    .times(1)
+                    .respond_with(status_code(200).body(body)),
+            );
+        }
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/search/images"))
+                .times(2)
+                .respond_with(json_encoded(data.clone())),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .build()
+            .unwrap();
+        let mirror = Mirror::new(dir.path()).layout(|img| format!("{}/image.png", img.id).into());
+
+        let report = cli.mirror("orca", &mirror).await.unwrap();
+        assert_eq!(report.downloaded, 4);
+        assert!(report.failed.is_empty());
+
+        let id = data["images"][0]["id"].as_i64().unwrap();
+        let file = std::fs::read(dir.path().join(format!("{}/image.png", id))).unwrap();
+        assert_eq!(file, format!("image {}", id).as_bytes());
+        let sidecar: crate::Image = serde_json::from_slice(
+            &std::fs::read(dir.path().join(format!("{}/image.png.json", id))).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(sidecar.id, id);
+
+        // the state file lets the second run skip everything
+        let report = cli.mirror("orca", &mirror).await.unwrap();
+        assert_eq!(report.downloaded, 0);
+        assert_eq!(report.skipped, 4);
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_images.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/search/images"))
+                .times(1)
+                .respond_with(json_encoded(data)),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .build()
+            .unwrap();
+
+        let mirror = Mirror::new(dir.path()).layout(|img| format!("../{}.png", img.id).into());
+        let report = cli.mirror("orca", &mirror).await.unwrap();
+        assert_eq!(report.failed.len(), 4);
+        assert!(matches!(&report.failed[0].1, Error::Layout(_)));
+
+        let state = dir.path().join("state.json");
+        std::fs::write(&state, "not json").unwrap();
+        let mirror = Mirror::new(dir.path()).state_file(&state);
+        match cli.mirror("orca", &mirror).await {
+            Err(Error::State { path, .. }) => assert_eq!(path, state),
+            result => panic!("wanted a state file error, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    search::{DEFAULT_PER_PAGE, MAX_PER_PAGE},
+    Result,
+};
+use futures_util::{
+    stream::{self, Stream},
+    StreamExt,
+};
+use std::future::Future;
+
+struct State<F> {
+    fetch: F,
+    skipped: u64,
+    page: u64,
+    seen: u64,
+    done: bool,
+}
+
+/// Lazily walk a paginated endpoint one page at a time, yielding each item.
+///
+/// `fetch` is called with the 1-based page number and returns the items on that page
+/// along with the total number of results, if the endpoint reports one. Walking stops
+/// at the first empty page, once `total` results have been seen, or after the first
+/// error.
+///
+/// `per_page` is the page size that was asked for, if any. It is used to work out how
+/// many results come before `start_page`, so it has to be the requested size rather
+/// than the length of whatever page happens to come back first.
+pub(crate) fn paginate<'a, T, F, Fut>(
+    start_page: u64,
+    per_page: Option<u64>,
+    fetch: F,
+) -> impl Stream<Item = Result<T>> + 'a
+where
+    T: 'a,
+    F: FnMut(u64) -> Fut + 'a,
+    Fut: Future<Output = Result<(Vec<T>, Option<u64>)>> + 'a,
+{
+    let start = start_page.max(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    let state = State {
+        fetch,
+        skipped: (start - 1) * per_page,
+        page: start,
+        seen: 0,
+        done: false,
+    };
+
+    stream::unfold(state, |mut st| async move {
+        if st.done {
+            return None;
+        }
+
+        match (st.fetch)(st.page).await {
+            Ok((items, total)) => {
+                if items.is_empty() {
+                    return None;
+                }
+
+                st.page += 1;
+                st.seen += items.len() as u64;
+
+                if let Some(total) = total {
+                    st.done = st.skipped + st.seen >= total;
+                }
+
+                Some((Ok(items), st))
+            }
+            Err(why) => {
+                st.done = true;
+                Some((Err(why), st))
+            }
+        }
+    })
+    .flat_map(|page| {
+        stream::iter(match page {
+            Ok(items) => items.into_iter().map(Ok).collect(),
+            Err(why) => vec![Err(why)],
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::paginate;
+    use futures_util::TryStreamExt;
+    use std::cell::RefCell;
+
+    #[tokio::test]
+    async fn short_first_page() {
+        // 5 results at 2 per page: page 3 is the last page and only has one result.
+        let fetched = RefCell::new(Vec::new());
+        let items: Vec<u64> = paginate(3, Some(2), |page| {
+            fetched.borrow_mut().push(page);
+            async move {
+                let items = if page == 3 { vec![5] } else { Vec::new() };
+                Ok((items, Some(5)))
+            }
+        })
+        .try_collect()
+        .await
+        .unwrap();
+
+        assert_eq!(items, vec![5]);
+        assert_eq!(*fetched.borrow(), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn clamped_per_page() {
+        // The boorus cap the page size at 50, whatever was asked for.
+        let fetched = RefCell::new(Vec::new());
+        let items: Vec<u64> = paginate(2, Some(100), |page| {
+            fetched.borrow_mut().push(page);
+            async move { Ok(((0..50).collect(), Some(100))) }
+        })
+        .try_collect()
+        .await
+        .unwrap();
+
+        assert_eq!(items.len(), 50);
+        assert_eq!(*fetched.borrow(), vec![2]);
+    }
+}
@@ -1,4 +1,10 @@
-use crate::Result;
+use crate::{
+    paginate::paginate,
+    search::{PostSearch, SearchPage},
+    timestamp::Timestamp,
+    Extra, Result,
+};
+use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,31 +15,38 @@ pub(crate) struct Response {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct ResponseList {
     posts: Vec<Post>,
+    #[serde(default)]
+    total: Option<u64>,
 }
 
 /// A forum post
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Post {
     pub author: String,
     pub avatar: String,
     pub body: String,
-    pub created_at: String,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: Timestamp,
     pub edit_reason: Option<String>,
-    pub edited_at: Option<String>,
+    #[serde(with = "crate::timestamp::option")]
+    pub edited_at: Option<Timestamp>,
     pub id: i64,
-    pub updated_at: String,
+    #[serde(with = "crate::timestamp")]
+    pub updated_at: Timestamp,
     pub user_id: Option<i64>,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
 }
 
 impl crate::Client {
     /// Fetches a forum post by ID.
     pub async fn post(&self, id: u64) -> Result<Post> {
         let resp: Response = self
-            .request(reqwest::Method::GET, &format!("api/v1/json/posts/{}", id))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            .execute(self.request(reqwest::Method::GET, &format!("api/v1/json/posts/{}", id)))
             .await?;
         Ok(resp.post)
     }
@@ -45,24 +58,88 @@ impl crate::Client {
         thread: T,
         page: u64,
     ) -> Result<Vec<Post>> {
-        let mut req = self.request(
-            reqwest::Method::GET,
-            &format!(
-                "api/v1/json/forums/{}/topics/{}/posts",
-                forum.into(),
-                thread.into()
-            ),
-        );
+        let path = thread_path(&forum.into(), &thread.into());
+        Ok(self.forum_thread_page(&path, page).await?.posts)
+    }
+
+    /// Fetches every post in a thread in a forum, lazily fetching each page starting
+    /// at the one [`Client::forum_thread`](crate::Client::forum_thread) would fetch.
+    pub fn forum_thread_stream<T: Into<String>>(
+        &self,
+        forum: T,
+        thread: T,
+        page: u64,
+    ) -> impl Stream<Item = Result<Post>> + '_ {
+        let path = thread_path(&forum.into(), &thread.into());
+        paginate(page, None, move |page| {
+            let path = path.clone();
+            async move {
+                let resp = self.forum_thread_page(&path, page).await?;
+                Ok((resp.posts, resp.total))
+            }
+        })
+    }
+
+    /// Search for forum posts.
+    pub async fn post_search<T: Into<String>>(&self, query: T, page: u64) -> Result<Vec<Post>> {
+        Ok(self
+            .search_posts(PostSearch::new(query).page(page))
+            .await?
+            .items)
+    }
+
+    /// Search for forum posts with paging options.
+    pub async fn search_posts<S: Into<PostSearch>>(&self, search: S) -> Result<SearchPage<Post>> {
+        let search = search.into();
+        let resp = self.post_search_page(&search).await?;
+        Ok(SearchPage::new(
+            resp.posts,
+            resp.total,
+            search.page,
+            search.per_page,
+            None,
+        ))
+    }
+
+    /// Search for forum posts, lazily fetching every page of results starting at the
+    /// search's page.
+    pub fn post_search_stream<S: Into<PostSearch>>(
+        &self,
+        search: S,
+    ) -> impl Stream<Item = Result<Post>> + '_ {
+        let search = search.into();
+        paginate(search.page.unwrap_or(1), search.per_page, move |page| {
+            let search = search.clone().page(page);
+            async move {
+                let resp = self.post_search_page(&search).await?;
+                Ok((resp.posts, resp.total))
+            }
+        })
+    }
+
+    async fn post_search_page(&self, search: &PostSearch) -> Result<ResponseList> {
+        let req = self
+            .request(reqwest::Method::GET, "api/v1/json/search/posts")
+            .query(&search.params());
+
+        self.execute(req).await
+    }
+
+    async fn forum_thread_page(&self, path: &str, page: u64) -> Result<ResponseList> {
+        let mut req = self.request(reqwest::Method::GET, path);
 
         if page != 0 {
             req = req.query(&[("page", format!("{}", page))])
         }
 
-        let resp: ResponseList = req.send().await?.error_for_status()?.json().await?;
-        Ok(resp.posts)
+        self.execute(req).await
     }
 }
 
+fn thread_path(forum: &str, thread: &str) -> String {
+    format!("api/v1/json/forums/{}/topics/{}/posts", forum, thread)
+}
+
 #[cfg(test)]
 mod tests {
     use httptest::{matchers::*, responders::*, Expectation, Server};
@@ -104,4 +181,28 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn search_posts() {
+        let _ = pretty_env_logger::try_init();
+        // There's no captured post search response in testdata, so return the one real
+        // post we have.
+        let post: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/post_1002.json")).unwrap();
+        let data = serde_json::json!({ "posts": [post["post"]] });
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/posts"),
+                request::query(url_decoded(contains(("q", "forum:dis, author:Alicia")))),
+            ])
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let page = cli.search_posts("forum:dis, author:Alicia").await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, 1002);
+    }
 }
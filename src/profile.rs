@@ -1,5 +1,11 @@
-use crate::Result;
+use crate::{
+    query::{Query, RangeOp},
+    search::{CommentSearch, ImageSearch, PostSearch},
+    timestamp::Timestamp,
+    Error, Extra, Result,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct Response {
@@ -8,12 +14,15 @@ struct Response {
 
 /// User profile information.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct User {
     pub avatar_url: String,
     pub awards: Vec<Award>,
     pub comments_count: i64,
-    pub created_at: String,
-    pub description: ::serde_json::Value,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: Timestamp,
+    pub description: Option<String>,
     pub id: i64,
     pub links: Vec<Link>,
     pub name: String,
@@ -22,12 +31,17 @@ pub struct User {
     pub slug: String,
     pub topics_count: i64,
     pub uploads_count: i64,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
 }
 
 /// A badge that was awarded to a user.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Award {
-    pub awarded_on: String,
+    #[serde(with = "crate::timestamp")]
+    pub awarded_on: Timestamp,
     pub id: i64,
     pub image_url: String,
     pub label: Option<String>,
@@ -37,7 +51,8 @@ pub struct Award {
 /// Tags that this user is linked to.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Link {
-    pub created_at: String,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: Timestamp,
     pub state: String,
     pub tag_id: i64,
     pub user_id: i64,
@@ -47,21 +62,149 @@ impl crate::Client {
     /// Get information about a user's profile by ID.
     pub async fn profile(&self, id: u64) -> Result<User> {
         let resp: Response = self
-            .request(
+            .execute(self.request(
                 reqwest::Method::GET,
                 &format!("api/v1/json/profiles/{}", id),
-            )
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            ))
             .await?;
         Ok(resp.user)
     }
+
+    /// Get information about a user's profile by their name, or by the slug in their
+    /// profile's URL.
+    ///
+    /// The API can only look up profiles by ID, so this finds the ID through the
+    /// user's comments, forum posts and uploads. Users that have never done any of
+    /// these in public can't be found this way and give [`Error::NotFound`].
+    pub async fn profile_by_name<T: AsRef<str>>(&self, name: T) -> Result<User> {
+        let name = name.as_ref();
+        let mut names = vec![name.to_string()];
+        let unslugged = unslug(name);
+        if unslugged != name {
+            names.push(unslugged);
+        }
+
+        for name in names {
+            if let Some(id) = self.user_id_by_name(&name).await? {
+                let user = self.profile(id).await?;
+                if user.name.eq_ignore_ascii_case(&name) {
+                    return Ok(user);
+                }
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+
+    /// Get information about the profile of the user that owns the API key, such as
+    /// to make sure a bot is running with the right key.
+    ///
+    /// The API has no call for this, so this is a best-effort guess: the owner is
+    /// worked out from the key's own filters, votes, faves and uploads. Every one of
+    /// those has to name the same user. If none of them name anyone, like for a brand
+    /// new account, or they name different users, this fails with
+    /// [`Error::UnknownIdentity`] instead of guessing.
+    pub async fn whoami(&self) -> Result<User> {
+        self.require_key()?;
+
+        let mut ids = BTreeSet::new();
+        let filters = self.user_filters(1).await?;
+        ids.extend(filters.iter().filter_map(|f| f.user_id));
+
+        let votes = self
+            .search_images(ImageSearch::new("my:faves || my:upvotes || my:downvotes").per_page(1))
+            .await?;
+        ids.extend(
+            votes
+                .interactions
+                .unwrap_or_default()
+                .iter()
+                .map(|i| i.user_id),
+        );
+
+        let uploads = self
+            .search_images(ImageSearch::new("my:uploads").per_page(1))
+            .await?;
+        ids.extend(uploads.items.iter().filter_map(|img| img.uploader_id));
+
+        let mut ids = ids.into_iter();
+        match (ids.next(), ids.next()) {
+            (Some(id), None) => self.profile(id as u64).await,
+            _ => Err(Error::UnknownIdentity),
+        }
+    }
+
+    async fn user_id_by_name(&self, name: &str) -> Result<Option<u64>> {
+        let author = Query::field("author", RangeOp::Eq, name).to_string();
+
+        let comments = self
+            .search_comments(CommentSearch::new(author.clone()).per_page(1))
+            .await?;
+        if let Some(id) = comments.items.first().and_then(|c| c.user_id) {
+            return Ok(Some(id as u64));
+        }
+
+        let posts = self
+            .search_posts(PostSearch::new(author).per_page(1))
+            .await?;
+        if let Some(id) = posts.items.first().and_then(|p| p.user_id) {
+            return Ok(Some(id as u64));
+        }
+
+        let uploader = Query::field("uploader", RangeOp::Eq, name).to_string();
+        let images = self
+            .search_images(ImageSearch::new(uploader).per_page(1))
+            .await?;
+        Ok(images
+            .items
+            .first()
+            .and_then(|img| img.uploader_id)
+            .map(|id| id as u64))
+    }
+}
+
+/// Turn a profile slug back into the user's name, undoing what Philomena does to
+/// make names safe for URLs.
+///
+/// Philomena escapes `-` as `-dash-` before escaping anything else, so a name can
+/// hold text that looks like an escape, like `a-dot-b`, which slugs to
+/// `a-dash-dot-dash-b`. Escapes are decoded in one pass from left to right so those
+/// come back as they were.
+fn unslug(slug: &str) -> String {
+    const ESCAPES: &[(&str, char)] = &[
+        ("-dash-", '-'),
+        ("-dot-", '.'),
+        ("-plus-", '+'),
+        ("-colon-", ':'),
+        ("-fwslash-", '/'),
+        ("-bwslash-", '\\'),
+    ];
+
+    let decoded: String = url::form_urlencoded::parse(format!("x={}", slug).as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_else(|| slug.to_string());
+
+    let mut name = String::with_capacity(decoded.len());
+    let mut rest = decoded.as_str();
+    while let Some(c) = rest.chars().next() {
+        match ESCAPES.iter().find(|(from, _)| rest.starts_with(from)) {
+            Some((from, to)) => {
+                name.push(*to);
+                rest = &rest[from.len()..];
+            }
+            None => {
+                name.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    name
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::Error;
     use httptest::{matchers::*, responders::*, Expectation, Server};
 
     #[tokio::test]
@@ -77,6 +220,127 @@ mod tests {
 
         let cli =
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
-        cli.profile(237).await.unwrap();
+        let user = cli.profile(237).await.unwrap();
+        assert_eq!(user.description, None);
+    }
+
+    #[test]
+    fn unslug() {
+        assert_eq!(super::unslug("Alicia"), "Alicia");
+        assert_eq!(super::unslug("Moon+Flower"), "Moon Flower");
+        assert_eq!(
+            super::unslug("Princess-dash-Luna-dot-bot"),
+            "Princess-Luna.bot"
+        );
+        assert_eq!(super::unslug("a-dash-dot-dash-b"), "a-dot-b");
+        assert_eq!(super::unslug("-dash--dot-"), "-.");
+    }
+
+    #[tokio::test]
+    async fn profile_by_name() {
+        let _ = pretty_env_logger::try_init();
+        let mut comments: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_comments.json")).unwrap();
+        comments["comments"][0]["author"] = "Alicia".into();
+        comments["comments"][0]["user_id"] = 237.into();
+        let profile: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/profile_237.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/comments"),
+                request::query(url_decoded(contains(("q", "author:Alicia")))),
+            ])
+            .respond_with(json_encoded(comments)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/profiles/237"))
+                .respond_with(json_encoded(profile)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        assert_eq!(cli.profile_by_name("Alicia").await.unwrap().id, 237);
+    }
+
+    fn whoami_server(filter_owner: Option<i64>, uploader: Option<i64>) -> Server {
+        // A filter owned by the key owner, written by hand. User filters have the same
+        // fields as the system filters in filters_system.json, with user_id set.
+        let filters = serde_json::json!({
+            "filters": [{
+                "id": 1028,
+                "name": "Orcas only",
+                "description": "",
+                "user_id": filter_owner,
+                "user_count": 1,
+                "system": false,
+                "public": false,
+                "spoilered_tag_ids": [],
+                "spoilered_complex": null,
+                "hidden_tag_ids": [],
+                "hidden_complex": null,
+            }],
+            "total": 1,
+        });
+        let mut uploads: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_images.json")).unwrap();
+        let mut upload = uploads["images"][0].clone();
+        upload["uploader_id"] = uploader.into();
+        uploads["images"] = serde_json::json!([upload]);
+        let profile: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/profile_237.json")).unwrap();
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/filters/user"))
+                .respond_with(json_encoded(filters)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/images"),
+                request::query(url_decoded(contains((
+                    "q",
+                    "my:faves || my:upvotes || my:downvotes"
+                )))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "images": [],
+                "interactions": [],
+                "total": 0,
+            }))),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/search/images"),
+                request::query(url_decoded(contains(("q", "my:uploads")))),
+            ])
+            .respond_with(json_encoded(uploads)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/profiles/237"))
+                .times(..)
+                .respond_with(json_encoded(profile)),
+        );
+        server
+    }
+
+    #[tokio::test]
+    async fn whoami() {
+        let _ = pretty_env_logger::try_init();
+        let server = whoami_server(Some(237), Some(237));
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        assert_eq!(cli.whoami().await.unwrap().name, "Alicia");
+    }
+
+    #[tokio::test]
+    async fn whoami_unknown() {
+        let _ = pretty_env_logger::try_init();
+        for (filter_owner, uploader) in [(None, None), (Some(237), Some(42))] {
+            let server = whoami_server(filter_owner, uploader);
+            let cli = crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/")))
+                .unwrap();
+            assert!(matches!(cli.whoami().await, Err(Error::UnknownIdentity)));
+        }
     }
 }
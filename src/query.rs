@@ -0,0 +1,1050 @@
+/*!
+A parser for Philomena's search language.
+
+Search strings like the ones passed to [`Client::image_search`](crate::Client::image_search)
+or stored in [`Filter::hidden_complex`](crate::Filter::hidden_complex) can be parsed into a
+[`Query`], inspected, changed and printed back out:
+
+```
+use furbooru::query::Query;
+
+let q: Query = "safe, (artist:atryl || score.gte:100), -oc:*".parse().unwrap();
+assert_eq!(q.to_string(), "safe, (artist:atryl || score.gte:100), -oc:*");
+```
+
+The language is made of terms joined by operators:
+
+* `a, b`, `a AND b` and `a && b` match when both sides match.
+* `a || b` and `a OR b` match when either side matches. AND binds tighter than OR.
+* `-a`, `!a` and `NOT a` match when `a` doesn't.
+* `( ... )` groups terms.
+
+A term is a tag such as `artist:atryl` or `solo female`, or a `field:value` pair when
+the part before the colon names a known field. Numeric and date fields also take the
+range operators `field.gt:`, `field.gte:`, `field.lt:` and `field.lte:`. `*` and `?`
+are wildcards, a backslash escapes the next character, double quotes keep operators
+from being interpreted and `^2` or `~0.5` at the end of a term set its boost or fuzz.
+
+Parse errors carry the [`Span`] of the input they are about, so they can be pointed at
+when linting queries people type in.
+*/
+
+use std::{fmt, str::FromStr};
+
+/// A range of byte offsets in the query string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Get the part of `src` this span covers.
+    pub fn slice<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.start..self.end]
+    }
+}
+
+/// What kind of values a search field holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Integer,
+    Float,
+    Date,
+    Boolean,
+    /// Matched exactly, with wildcards.
+    Literal,
+    /// Matched against words of free-form text.
+    Text,
+}
+
+impl FieldKind {
+    /// Returns true if range operators like `.gte` can be used with this field.
+    pub fn is_ranged(&self) -> bool {
+        matches!(
+            self,
+            FieldKind::Integer | FieldKind::Float | FieldKind::Date
+        )
+    }
+}
+
+/// A field that can be searched with `name:value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub kind: FieldKind,
+}
+
+const fn field(name: &'static str, kind: FieldKind) -> Field {
+    Field { name, kind }
+}
+
+/// The fields Philomena's image search understands.
+pub static IMAGE_FIELDS: &[Field] = &[
+    field("id", FieldKind::Integer),
+    field("width", FieldKind::Integer),
+    field("height", FieldKind::Integer),
+    field("comment_count", FieldKind::Integer),
+    field("score", FieldKind::Integer),
+    field("upvotes", FieldKind::Integer),
+    field("downvotes", FieldKind::Integer),
+    field("faves", FieldKind::Integer),
+    field("uploader_id", FieldKind::Integer),
+    field("faved_by_id", FieldKind::Integer),
+    field("tag_count", FieldKind::Integer),
+    field("pixels", FieldKind::Integer),
+    field("size", FieldKind::Integer),
+    field("source_count", FieldKind::Integer),
+    field("gallery_id", FieldKind::Integer),
+    field("aspect_ratio", FieldKind::Float),
+    field("wilson_score", FieldKind::Float),
+    field("duration", FieldKind::Float),
+    field("created_at", FieldKind::Date),
+    field("updated_at", FieldKind::Date),
+    field("first_seen_at", FieldKind::Date),
+    field("animated", FieldKind::Boolean),
+    field("processed", FieldKind::Boolean),
+    field("thumbnails_generated", FieldKind::Boolean),
+    field("faved_by", FieldKind::Literal),
+    field("orig_sha512_hash", FieldKind::Literal),
+    field("sha512_hash", FieldKind::Literal),
+    field("uploader", FieldKind::Literal),
+    field("source_url", FieldKind::Literal),
+    field("original_format", FieldKind::Literal),
+    field("format", FieldKind::Literal),
+    field("mime_type", FieldKind::Literal),
+    field("file_name", FieldKind::Literal),
+    field("my", FieldKind::Literal),
+    field("description", FieldKind::Text),
+];
+
+/// A parsed search query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(Term),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+/// A single term of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub kind: TermKind,
+    pub boost: Option<f64>,
+    pub fuzz: Option<f64>,
+    /// Where this term was in the query string. Terms that weren't parsed have an
+    /// empty span.
+    pub span: Span,
+}
+
+/// What a term matches against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TermKind {
+    /// A tag name, like `safe` or `artist:atryl`.
+    Tag(Pattern),
+    /// A field, like `score.gte:100`.
+    Field {
+        name: String,
+        op: RangeOp,
+        value: Pattern,
+    },
+}
+
+/// How a field term compares its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeOp {
+    /// `field:value`
+    Eq,
+    /// `field.gt:value`
+    Gt,
+    /// `field.gte:value`
+    Gte,
+    /// `field.lt:value`
+    Lt,
+    /// `field.lte:value`
+    Lte,
+}
+
+impl RangeOp {
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "gt" => Some(RangeOp::Gt),
+            "gte" => Some(RangeOp::Gte),
+            "lt" => Some(RangeOp::Lt),
+            "lte" => Some(RangeOp::Lte),
+            _ => None,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            RangeOp::Eq => "",
+            RangeOp::Gt => ".gt",
+            RangeOp::Gte => ".gte",
+            RangeOp::Lt => ".lt",
+            RangeOp::Lte => ".lte",
+        }
+    }
+}
+
+/// A piece of a [`Pattern`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternPart {
+    Literal(String),
+    /// `*`, any run of characters.
+    AnyString,
+    /// `?`, any single character.
+    AnyChar,
+}
+
+/// A term value that may contain wildcards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pattern {
+    parts: Vec<PatternPart>,
+}
+
+impl Pattern {
+    /// A pattern that matches `text` exactly.
+    pub fn literal<T: Into<String>>(text: T) -> Self {
+        let text = text.into();
+        if text.is_empty() {
+            return Self::default();
+        }
+        Self {
+            parts: vec![PatternPart::Literal(text)],
+        }
+    }
+
+    pub fn parts(&self) -> &[PatternPart] {
+        &self.parts
+    }
+
+    /// Get the pattern's text if it has no wildcards.
+    pub fn as_literal(&self) -> Option<String> {
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                PatternPart::Literal(lit) => text.push_str(lit),
+                _ => return None,
+            }
+        }
+        Some(text)
+    }
+
+    fn push(&mut self, ch: char, escaped: bool) {
+        match (ch, escaped) {
+            ('*', false) => self.parts.push(PatternPart::AnyString),
+            ('?', false) => self.parts.push(PatternPart::AnyChar),
+            _ => match self.parts.last_mut() {
+                Some(PatternPart::Literal(lit)) => lit.push(ch),
+                _ => self.parts.push(PatternPart::Literal(ch.to_string())),
+            },
+        }
+    }
+
+    fn from_chars(chars: &[(char, bool)]) -> Self {
+        let mut pattern = Self::default();
+        for (ch, escaped) in chars {
+            pattern.push(*ch, *escaped);
+        }
+        pattern
+    }
+}
+
+impl fmt::Display for Pattern {
+    /// Writes the pattern with wildcards and backslashes escaped, but without any
+    /// quoting.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                PatternPart::AnyString => f.write_str("*")?,
+                PatternPart::AnyChar => f.write_str("?")?,
+                PatternPart::Literal(lit) => {
+                    for ch in lit.chars() {
+                        if matches!(ch, '*' | '?' | '\\' | '"') {
+                            f.write_str("\\")?;
+                        }
+                        write!(f, "{}", ch)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What went wrong while parsing a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A term was expected, but an operator or the end of the query was found.
+    ExpectedTerm,
+    /// Something other than an operator followed a term.
+    ExpectedOperator,
+    /// A `(` was never closed.
+    UnclosedParen,
+    /// A `)` had no matching `(`.
+    UnmatchedParen,
+    /// A `"` was never closed.
+    UnterminatedQuote,
+    /// The query ended with a backslash.
+    TrailingEscape,
+    /// A range operator was used with a field that doesn't support ranges.
+    InvalidRange { field: String },
+    /// A field was given a value of the wrong type.
+    InvalidValue { field: String, kind: FieldKind },
+}
+
+/// An error found while parsing a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::ExpectedTerm => write!(f, "expected a term")?,
+            ParseErrorKind::ExpectedOperator => write!(f, "expected an operator")?,
+            ParseErrorKind::UnclosedParen => write!(f, "unclosed parenthesis")?,
+            ParseErrorKind::UnmatchedParen => write!(f, "unmatched closing parenthesis")?,
+            ParseErrorKind::UnterminatedQuote => write!(f, "unterminated quote")?,
+            ParseErrorKind::TrailingEscape => write!(f, "trailing backslash")?,
+            ParseErrorKind::InvalidRange { field } => {
+                write!(f, "field {} can't be used with a range operator", field)?
+            }
+            ParseErrorKind::InvalidValue { field, kind } => {
+                write!(f, "field {} needs a {:?} value", field, kind)?
+            }
+        };
+        write!(f, " at {}..{}", self.span.start, self.span.end)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+fn err<T>(kind: ParseErrorKind, span: Span) -> ParseResult<T> {
+    Err(ParseError { kind, span })
+}
+
+impl Query {
+    /// Parse an image search query.
+    pub fn parse(src: &str) -> ParseResult<Self> {
+        Self::parse_with_fields(src, IMAGE_FIELDS)
+    }
+
+    /// Parse a query, treating `name:value` terms as fields only when `name` is one of
+    /// `fields`. Everything else with a colon in it is a namespaced tag.
+    pub fn parse_with_fields(src: &str, fields: &[Field]) -> ParseResult<Self> {
+        let tokens = lex(src)?;
+        let mut parser = Parser {
+            src,
+            fields,
+            tokens,
+            pos: 0,
+        };
+        let query = parser.or_expr()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(Token {
+                kind: TokenKind::RParen,
+                span,
+            }) => err(ParseErrorKind::UnmatchedParen, *span),
+            Some(tok) => err(ParseErrorKind::ExpectedOperator, tok.span),
+        }
+    }
+
+    /// Create a query matching a single tag.
+    pub fn tag<T: Into<String>>(name: T) -> Self {
+        Query::Term(Term {
+            kind: TermKind::Tag(Pattern::literal(name)),
+            boost: None,
+            fuzz: None,
+            span: Span::default(),
+        })
+    }
+
+    /// Create a query matching a single field, like `author:Alicia`.
+    pub fn field<N: Into<String>, V: Into<String>>(name: N, op: RangeOp, value: V) -> Self {
+        Query::Term(Term {
+            kind: TermKind::Field {
+                name: name.into(),
+                op,
+                value: Pattern::literal(value),
+            },
+            boost: None,
+            fuzz: None,
+            span: Span::default(),
+        })
+    }
+
+    /// Get every term in this query, in order.
+    pub fn terms(&self) -> Vec<&Term> {
+        let mut terms = vec![];
+        self.collect_terms(&mut terms);
+        terms
+    }
+
+    fn collect_terms<'a>(&'a self, terms: &mut Vec<&'a Term>) {
+        match self {
+            Query::Term(term) => terms.push(term),
+            Query::Not(q) => q.collect_terms(terms),
+            Query::And(qs) | Query::Or(qs) => qs.iter().for_each(|q| q.collect_terms(terms)),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Query::Or(_) => 0,
+            Query::And(_) => 1,
+            Query::Not(_) | Query::Term(_) => 2,
+        }
+    }
+
+    fn write_child(&self, f: &mut fmt::Formatter<'_>, child: &Query) -> fmt::Result {
+        if child.precedence() < self.precedence()
+            || (child.precedence() == self.precedence() && !matches!(child, Query::Term(_)))
+        {
+            write!(f, "({})", child)
+        } else {
+            write!(f, "{}", child)
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> ParseResult<Self> {
+        Self::parse(src)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Term(term) => write!(f, "{}", term),
+            Query::Not(q) => {
+                f.write_str("-")?;
+                if matches!(**q, Query::And(_) | Query::Or(_)) {
+                    write!(f, "({})", q)
+                } else {
+                    write!(f, "{}", q)
+                }
+            }
+            Query::And(qs) | Query::Or(qs) => {
+                let sep = if matches!(self, Query::And(_)) {
+                    ", "
+                } else {
+                    " || "
+                };
+                for (i, q) in qs.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(sep)?;
+                    }
+                    self.write_child(f, q)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match &self.kind {
+            TermKind::Tag(pattern) => pattern.to_string(),
+            TermKind::Field { name, op, value } => format!("{}{}:{}", name, op.suffix(), value),
+        };
+
+        if needs_quotes(&text) {
+            write!(f, "\"{}\"", text)?;
+        } else {
+            f.write_str(&text)?;
+        }
+        if let Some(boost) = self.boost {
+            write!(f, "^{}", boost)?;
+        }
+        if let Some(fuzz) = self.fuzz {
+            write!(f, "~{}", fuzz)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if `text` would not lex back into a single bare term.
+fn needs_quotes(text: &str) -> bool {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || trimmed.len() != text.len() {
+        return true;
+    }
+    if text.starts_with(['-', '!', '(', '"'])
+        || text.contains(',')
+        || suffix_start(&unescape(text)).is_some()
+        || text.contains("&&")
+        || text.contains("||")
+        || starts_with_keyword(text, "AND")
+        || starts_with_keyword(text, "OR")
+        || starts_with_keyword(text, "NOT")
+    {
+        return true;
+    }
+
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (i, ch) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => return true,
+            ')' => depth -= 1,
+            c if c.is_whitespace() && keyword_follows(&text[i..]) => return true,
+            _ => {}
+        }
+    }
+    depth != 0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+/// Returns true if `rest` starts with `word` followed by whitespace, `(` or nothing.
+fn starts_with_keyword(rest: &str, word: &str) -> bool {
+    match rest.strip_prefix(word) {
+        Some(after) => {
+            after.is_empty() || after.starts_with(|c: char| c.is_whitespace() || c == '(')
+        }
+        None => false,
+    }
+}
+
+/// Returns true if `rest`, which starts with whitespace, continues with an infix
+/// `AND` or `OR` keyword.
+fn keyword_follows(rest: &str) -> bool {
+    let after = rest.trim_start();
+    after.len() != rest.len()
+        && (starts_with_keyword(after, "AND") || starts_with_keyword(after, "OR"))
+}
+
+fn lex(src: &str) -> ParseResult<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    loop {
+        let rest = &src[pos..];
+        let trimmed = rest.trim_start();
+        pos += rest.len() - trimmed.len();
+        let rest = trimmed;
+
+        let mut chars = rest.chars();
+        let first = match chars.next() {
+            Some(ch) => ch,
+            None => break,
+        };
+        let second = chars.next();
+
+        let (kind, len) = if first == '(' {
+            (TokenKind::LParen, 1)
+        } else if first == ')' {
+            (TokenKind::RParen, 1)
+        } else if first == ',' {
+            (TokenKind::And, 1)
+        } else if rest.starts_with("&&") {
+            (TokenKind::And, 2)
+        } else if rest.starts_with("||") {
+            (TokenKind::Or, 2)
+        } else if (first == '-' || first == '!') && second.is_some_and(|c| !c.is_whitespace()) {
+            (TokenKind::Not, 1)
+        } else if starts_with_keyword(rest, "AND") {
+            (TokenKind::And, 3)
+        } else if starts_with_keyword(rest, "OR") {
+            (TokenKind::Or, 2)
+        } else if starts_with_keyword(rest, "NOT") {
+            (TokenKind::Not, 3)
+        } else if first == '"' {
+            (TokenKind::Term, lex_quoted(src, pos)?)
+        } else {
+            (TokenKind::Term, lex_bare(src, pos)?)
+        };
+
+        tokens.push(Token {
+            kind,
+            span: Span::new(pos, pos + len),
+        });
+        pos += len;
+    }
+
+    Ok(tokens)
+}
+
+/// Find the length of the quoted term starting at `start`, including any boost or
+/// fuzz after the closing quote.
+fn lex_quoted(src: &str, start: usize) -> ParseResult<usize> {
+    let rest = &src[start..];
+    let mut escaped = false;
+    let mut close = None;
+    for (i, ch) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            close = Some(i + 1);
+            break;
+        }
+    }
+
+    let close = match close {
+        Some(close) => close,
+        None => {
+            return err(
+                ParseErrorKind::UnterminatedQuote,
+                Span::new(start, src.len()),
+            )
+        }
+    };
+
+    let suffix = rest[close..]
+        .find(|c: char| c.is_whitespace() || matches!(c, ',' | ')' | '&' | '|'))
+        .unwrap_or(rest.len() - close);
+    Ok(close + suffix)
+}
+
+/// Find the length of the unquoted term starting at `start`.
+fn lex_bare(src: &str, start: usize) -> ParseResult<usize> {
+    let rest = &src[start..];
+    let mut depth = 0usize;
+    let mut escaped = false;
+    let mut end = rest.len();
+
+    for (i, ch) in rest.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                end = i;
+                break;
+            }
+            ')' => depth -= 1,
+            ',' => {
+                end = i;
+                break;
+            }
+            '&' | '|' if rest[i..].starts_with("&&") || rest[i..].starts_with("||") => {
+                end = i;
+                break;
+            }
+            c if c.is_whitespace() && keyword_follows(&rest[i..]) => {
+                end = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    if escaped {
+        return err(
+            ParseErrorKind::TrailingEscape,
+            Span::new(start + end - 1, start + end),
+        );
+    }
+
+    Ok(rest[..end].trim_end().len())
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    fields: &'a [Field],
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        match self.peek() {
+            Some(tok) if tok.kind == kind => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn end_span(&self) -> Span {
+        Span::new(self.src.len(), self.src.len())
+    }
+
+    fn or_expr(&mut self) -> ParseResult<Query> {
+        let mut qs = vec![self.and_expr()?];
+        while self.eat(TokenKind::Or) {
+            match self.and_expr()? {
+                Query::Or(inner) => qs.extend(inner),
+                q => qs.push(q),
+            }
+        }
+        Ok(if qs.len() == 1 {
+            qs.remove(0)
+        } else {
+            Query::Or(qs)
+        })
+    }
+
+    fn and_expr(&mut self) -> ParseResult<Query> {
+        let mut qs = vec![];
+        loop {
+            match self.not_expr()? {
+                Query::And(inner) => qs.extend(inner),
+                q => qs.push(q),
+            }
+            if !self.eat(TokenKind::And) {
+                break;
+            }
+        }
+        Ok(if qs.len() == 1 {
+            qs.remove(0)
+        } else {
+            Query::And(qs)
+        })
+    }
+
+    fn not_expr(&mut self) -> ParseResult<Query> {
+        if self.eat(TokenKind::Not) {
+            return Ok(Query::Not(Box::new(self.not_expr()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> ParseResult<Query> {
+        let tok = match self.peek() {
+            Some(tok) => tok.clone(),
+            None => return err(ParseErrorKind::ExpectedTerm, self.end_span()),
+        };
+        self.pos += 1;
+
+        match tok.kind {
+            TokenKind::LParen => {
+                let q = self.or_expr()?;
+                if !self.eat(TokenKind::RParen) {
+                    return err(ParseErrorKind::UnclosedParen, tok.span);
+                }
+                Ok(q)
+            }
+            TokenKind::Term => Ok(Query::Term(self.term(tok.span)?)),
+            TokenKind::RParen => err(ParseErrorKind::UnmatchedParen, tok.span),
+            _ => err(ParseErrorKind::ExpectedTerm, tok.span),
+        }
+    }
+
+    fn term(&self, span: Span) -> ParseResult<Term> {
+        let text = span.slice(self.src);
+        let mut boost = None;
+        let mut fuzz = None;
+
+        let mut chars = if let Some(quoted) = text.strip_prefix('"') {
+            let close = quoted.rfind('"').unwrap_or(quoted.len());
+            parse_suffix(&quoted[close + 1..], &mut boost, &mut fuzz);
+            unescape(&quoted[..close])
+        } else {
+            let mut chars = unescape(text);
+            if let Some(at) = suffix_start(&chars) {
+                let suffix: String = chars[at..].iter().map(|(ch, _)| ch).collect();
+                parse_suffix(&suffix, &mut boost, &mut fuzz);
+                chars.truncate(at);
+            }
+            chars
+        };
+        trim_chars(&mut chars);
+
+        let kind = self.term_kind(&chars, span)?;
+        Ok(Term {
+            kind,
+            boost,
+            fuzz,
+            span,
+        })
+    }
+
+    fn term_kind(&self, chars: &[(char, bool)], span: Span) -> ParseResult<TermKind> {
+        let colon = match chars.iter().position(|c| *c == (':', false)) {
+            Some(colon) => colon,
+            None => return Ok(TermKind::Tag(Pattern::from_chars(chars))),
+        };
+
+        let prefix: String = chars[..colon].iter().map(|(ch, _)| ch).collect();
+        let prefix = prefix.trim().to_lowercase();
+        let (name, op) = match prefix.rsplit_once('.') {
+            Some((name, suffix)) => match RangeOp::from_suffix(suffix) {
+                Some(op) => (name, op),
+                None => (prefix.as_str(), RangeOp::Eq),
+            },
+            None => (prefix.as_str(), RangeOp::Eq),
+        };
+
+        let field = match self.fields.iter().find(|field| field.name == name) {
+            Some(field) => field,
+            None => return Ok(TermKind::Tag(Pattern::from_chars(chars))),
+        };
+
+        if op != RangeOp::Eq && !field.kind.is_ranged() {
+            return err(
+                ParseErrorKind::InvalidRange {
+                    field: field.name.into(),
+                },
+                span,
+            );
+        }
+
+        let mut value = chars[colon + 1..].to_vec();
+        trim_chars(&mut value);
+        let value = Pattern::from_chars(&value);
+        let valid = match field.kind {
+            FieldKind::Integer => value.as_literal().is_some_and(|v| v.parse::<i64>().is_ok()),
+            FieldKind::Float => value.as_literal().is_some_and(|v| v.parse::<f64>().is_ok()),
+            FieldKind::Boolean => value
+                .as_literal()
+                .is_some_and(|v| matches!(v.to_lowercase().as_str(), "true" | "false")),
+            FieldKind::Date => value.as_literal().is_some_and(|v| !v.is_empty()),
+            FieldKind::Literal | FieldKind::Text => true,
+        };
+        if !valid {
+            return err(
+                ParseErrorKind::InvalidValue {
+                    field: field.name.into(),
+                    kind: field.kind,
+                },
+                span,
+            );
+        }
+
+        Ok(TermKind::Field {
+            name: field.name.into(),
+            op,
+            value,
+        })
+    }
+}
+
+/// Split `text` into characters, marking the ones that were escaped with a
+/// backslash.
+fn unescape(text: &str) -> Vec<(char, bool)> {
+    let mut chars = vec![];
+    let mut iter = text.chars();
+    while let Some(ch) = iter.next() {
+        if ch == '\\' {
+            if let Some(next) = iter.next() {
+                chars.push((next, true));
+            }
+        } else {
+            chars.push((ch, false));
+        }
+    }
+    chars
+}
+
+fn trim_chars(chars: &mut Vec<(char, bool)>) {
+    while matches!(chars.last(), Some((ch, false)) if ch.is_whitespace()) {
+        chars.pop();
+    }
+    let leading = chars
+        .iter()
+        .take_while(|(ch, escaped)| !escaped && ch.is_whitespace())
+        .count();
+    chars.drain(..leading);
+}
+
+/// Find where a trailing `^boost` or `~fuzz` starts, if there is one.
+fn suffix_start(chars: &[(char, bool)]) -> Option<usize> {
+    let at = chars
+        .iter()
+        .rposition(|c| *c == ('^', false) || *c == ('~', false))?;
+    let number: String = chars[at + 1..].iter().map(|(ch, _)| ch).collect();
+    if at != 0 && number.parse::<f64>().is_ok() {
+        Some(at)
+    } else {
+        None
+    }
+}
+
+fn parse_suffix(suffix: &str, boost: &mut Option<f64>, fuzz: &mut Option<f64>) {
+    if let Some(num) = suffix.strip_prefix('^') {
+        *boost = num.parse().ok();
+    } else if let Some(num) = suffix.strip_prefix('~') {
+        *fuzz = num.parse().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn without_spans(q: Query) -> Query {
+        match q {
+            Query::Term(term) => Query::Term(Term {
+                span: Span::default(),
+                ..term
+            }),
+            Query::Not(q) => Query::Not(Box::new(without_spans(*q))),
+            Query::And(qs) => Query::And(qs.into_iter().map(without_spans).collect()),
+            Query::Or(qs) => Query::Or(qs.into_iter().map(without_spans).collect()),
+        }
+    }
+
+    fn roundtrip(src: &str, want: &str) {
+        let q = Query::parse(src).unwrap();
+        assert_eq!(q.to_string(), want, "printing {:?}", src);
+        assert_eq!(
+            without_spans(Query::parse(want).unwrap()),
+            without_spans(q),
+            "reparsing {:?}",
+            want
+        );
+    }
+
+    #[test]
+    fn operators() {
+        roundtrip("safe, solo", "safe, solo");
+        roundtrip("safe AND solo && cute", "safe, solo, cute");
+        roundtrip(
+            "safe OR suggestive || questionable",
+            "safe || suggestive || questionable",
+        );
+        roundtrip("a || b, c", "a || b, c");
+        roundtrip("(a || b), c", "(a || b), c");
+        roundtrip("NOT a, !b, -c", "-a, -b, -c");
+        roundtrip("-(a, b)", "-(a, b)");
+        roundtrip("((a))", "a");
+    }
+
+    #[test]
+    fn terms() {
+        roundtrip("solo female", "solo female");
+        roundtrip("oc:cadey (cadey), safe", "oc:cadey (cadey), safe");
+        roundtrip("artist:*", "artist:*");
+        roundtrip(r"a\*b?", r"a\*b?");
+        roundtrip(r#""tag, with comma""#, r#""tag, with comma""#);
+        roundtrip("safe^2, cute~0.5", "safe^2, cute~0.5");
+        roundtrip("^_^", "^_^");
+        roundtrip(
+            "score.gte:100, created_at.lt:2020-01-01",
+            "score.gte:100, created_at.lt:2020-01-01",
+        );
+        roundtrip("Score.GTE: 100", "score.gte:100");
+    }
+
+    #[test]
+    fn fields() {
+        let q = Query::parse("score.gte:100").unwrap();
+        assert_eq!(
+            q.terms()[0].kind,
+            TermKind::Field {
+                name: "score".into(),
+                op: RangeOp::Gte,
+                value: Pattern::literal("100"),
+            }
+        );
+
+        let q = Query::parse("artist:atryl").unwrap();
+        assert_eq!(
+            q.terms()[0].kind,
+            TermKind::Tag(Pattern::literal("artist:atryl"))
+        );
+
+        let q = Query::parse("source_url:*twitter.com*").unwrap();
+        match &q.terms()[0].kind {
+            TermKind::Field { value, .. } => assert_eq!(
+                value.parts(),
+                &[
+                    PatternPart::AnyString,
+                    PatternPart::Literal("twitter.com".into()),
+                    PatternPart::AnyString,
+                ]
+            ),
+            kind => panic!("wanted a field, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let src = "safe, solo female || cute";
+        let q = Query::parse(src).unwrap();
+        let spans: Vec<&str> = q.terms().iter().map(|t| t.span.slice(src)).collect();
+        assert_eq!(spans, vec!["safe", "solo female", "cute"]);
+    }
+
+    #[test]
+    fn errors() {
+        let cases = vec![
+            ("", ParseErrorKind::ExpectedTerm, Span::new(0, 0)),
+            ("safe,", ParseErrorKind::ExpectedTerm, Span::new(5, 5)),
+            (
+                "(safe, solo",
+                ParseErrorKind::UnclosedParen,
+                Span::new(0, 1),
+            ),
+            ("safe)", ParseErrorKind::UnmatchedParen, Span::new(4, 5)),
+            (
+                r#"safe, "solo"#,
+                ParseErrorKind::UnterminatedQuote,
+                Span::new(6, 11),
+            ),
+            ("safe\\", ParseErrorKind::TrailingEscape, Span::new(4, 5)),
+            (
+                "safe, uploader.gt:bob",
+                ParseErrorKind::InvalidRange {
+                    field: "uploader".into(),
+                },
+                Span::new(6, 21),
+            ),
+            (
+                "score:lots",
+                ParseErrorKind::InvalidValue {
+                    field: "score".into(),
+                    kind: FieldKind::Integer,
+                },
+                Span::new(0, 10),
+            ),
+            ("(a) b", ParseErrorKind::ExpectedOperator, Span::new(4, 5)),
+        ];
+
+        for (src, kind, span) in cases {
+            assert_eq!(
+                Query::parse(src),
+                Err(ParseError { kind, span }),
+                "parsing {:?}",
+                src
+            );
+        }
+    }
+}
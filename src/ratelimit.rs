@@ -0,0 +1,246 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// How fast a rate limit bucket refills and how many requests it can burst.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    capacity: u32,
+    interval: Duration,
+}
+
+impl Quota {
+    /// Allow `count` requests every `period`, bursting up to `count` at once.
+    ///
+    /// Panics if `count` is zero.
+    pub fn new(count: u32, period: Duration) -> Self {
+        assert!(count > 0, "a quota must allow at least one request");
+        Self {
+            capacity: count,
+            interval: period / count,
+        }
+    }
+
+    /// Allow `count` requests per second.
+    pub fn per_second(count: u32) -> Self {
+        Self::new(count, Duration::from_secs(1))
+    }
+
+    /// Allow `count` requests per minute.
+    pub fn per_minute(count: u32) -> Self {
+        Self::new(count, Duration::from_secs(60))
+    }
+
+    /// Change how many requests can be made back to back before waiting.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.capacity = burst.max(1);
+        self
+    }
+}
+
+/// Which budget a request is charged against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    /// Requests that only read data, such as searches and lookups.
+    Read,
+    /// Requests that change data, such as uploading images.
+    Write,
+}
+
+impl RequestKind {
+    pub(crate) fn of(method: &reqwest::Method) -> Self {
+        match *method {
+            reqwest::Method::GET | reqwest::Method::HEAD => RequestKind::Read,
+            _ => RequestKind::Write,
+        }
+    }
+}
+
+/// A snapshot of one rate limit bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketStatus {
+    /// How many requests can be made right now without waiting.
+    pub available: u32,
+    /// How many requests the bucket holds when it is full.
+    pub capacity: u32,
+    /// How long until the next request can be made. This is zero when `available`
+    /// is not.
+    pub wait: Duration,
+}
+
+/// A snapshot of a [`RateLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitStatus {
+    pub read: BucketStatus,
+    pub write: BucketStatus,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(quota: Quota, now: Instant) -> Self {
+        Self {
+            quota,
+            tokens: quota.capacity as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() / self.quota.interval.as_secs_f64())
+            .min(self.quota.capacity as f64);
+        self.updated = now;
+    }
+
+    /// Take a token, going into debt if there are none left. Returns how long the
+    /// caller has to wait before its debt is paid off.
+    fn take(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        self.wait()
+    }
+
+    fn wait(&self) -> Duration {
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            self.quota.interval.mul_f64(-self.tokens)
+        }
+    }
+
+    fn status(&mut self, now: Instant) -> BucketStatus {
+        self.refill(now);
+        BucketStatus {
+            available: self.tokens.max(0.0).floor() as u32,
+            capacity: self.quota.capacity,
+            wait: if self.tokens >= 1.0 {
+                Duration::ZERO
+            } else {
+                self.quota.interval.mul_f64(1.0 - self.tokens)
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    read: Bucket,
+    write: Bucket,
+}
+
+/// A client-side token bucket rate limiter with separate budgets for reads and
+/// writes.
+///
+/// Cloning a limiter is cheap and every clone shares the same budgets, so one
+/// limiter can be handed to several [`Client`](crate::Client)s. Clones of a client
+/// share its limiter too.
+///
+/// ```
+/// use furbooru::ratelimit::{Quota, RateLimiter};
+///
+/// let limiter = RateLimiter::new(Quota::per_second(2), Quota::per_minute(1));
+/// let cli = furbooru::Client::builder()
+///     .user_agent("my-bot/0.1 (Alicia)")
+///     .rate_limiter(limiter.clone())
+///     .build()
+///     .unwrap();
+///
+/// println!("{:?}", limiter.status());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter with a budget for reads and a budget for writes.
+    pub fn new(read: Quota, write: Quota) -> Self {
+        let now = Instant::now();
+        Self {
+            buckets: Arc::new(Mutex::new(Buckets {
+                read: Bucket::new(read, now),
+                write: Bucket::new(write, now),
+            })),
+        }
+    }
+
+    /// Wait until a request of the given kind is allowed.
+    pub async fn acquire(&self, kind: RequestKind) {
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let now = Instant::now();
+            match kind {
+                RequestKind::Read => buckets.read.take(now),
+                RequestKind::Write => buckets.write.take(now),
+            }
+        };
+
+        if wait > Duration::ZERO {
+            log::debug!("rate limited: waiting {:?} for a {:?} token", wait, kind);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Get the current state of both budgets.
+    pub fn status(&self) -> RateLimitStatus {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        RateLimitStatus {
+            read: buckets.read.status(now),
+            write: buckets.write.status(now),
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    /// A conservative limiter: five reads per second and one write every ten seconds.
+    /// The boorus don't publish exact numbers, so tune this for your bot.
+    fn default() -> Self {
+        Self::new(Quota::per_second(5), Quota::new(1, Duration::from_secs(10)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_when_empty() {
+        let limiter = RateLimiter::new(Quota::per_second(2), Quota::per_second(1));
+        let start = Instant::now();
+
+        limiter.acquire(RequestKind::Read).await;
+        limiter.acquire(RequestKind::Read).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(limiter.status().read.available, 0);
+        assert_eq!(limiter.status().read.wait, Duration::from_millis(500));
+
+        limiter.acquire(RequestKind::Read).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+        // writes have their own budget
+        assert_eq!(limiter.status().write.available, 1);
+        limiter.acquire(RequestKind::Write).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shared_between_clones() {
+        let limiter = RateLimiter::new(Quota::per_second(1), Quota::per_second(1));
+        let other = limiter.clone();
+
+        limiter.acquire(RequestKind::Read).await;
+        assert_eq!(other.status().read.available, 0);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(other.status().read.available, 1);
+    }
+}
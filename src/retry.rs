@@ -0,0 +1,245 @@
+use crate::Error;
+use rand::Rng;
+use std::time::Duration;
+
+/// Controls how failed read requests are retried.
+///
+/// Only idempotent `GET` requests are ever retried. Calls that change data, such as
+/// [`Client::post_image`](crate::Client::post_image), are sent exactly once no matter
+/// what policy is set.
+///
+/// A request is retried when the booru rate limits it (HTTP 429), when the booru
+/// fails with a 5xx status code, or when the connection fails or times out. The
+/// delay between attempts grows exponentially from `base_delay` up to `max_delay`,
+/// with full jitter. If the booru sends a `Retry-After` header, that delay is used
+/// instead.
+///
+/// ```
+/// use furbooru::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let cli = furbooru::Client::builder()
+///     .user_agent("my-bot/0.1 (Alicia)")
+///     .retry_policy(
+///         RetryPolicy::new()
+///             .max_attempts(5)
+///             .deadline(Duration::from_secs(60)),
+///     )
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    deadline: Option<Duration>,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            deadline: None,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Three attempts, starting at half a second between them, with jitter and no
+    /// deadline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many times a request is sent in total, including the first attempt.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the delay before the first retry. Each later retry doubles it.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Set the longest delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Give up retrying once this much time has passed since the first attempt. A
+    /// retry that would have to wait past the deadline is not attempted.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Turn jitter on or off. Turning it off makes delays predictable, which is
+    /// mostly useful in tests.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub(crate) fn max_attempts_allowed(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns true if the request that failed with this error is worth sending
+    /// again.
+    pub(crate) fn is_retryable(&self, why: &Error) -> bool {
+        match why {
+            Error::RateLimited { .. } => true,
+            Error::Server(status) => status.is_server_error(),
+            Error::Transport(why) => why.is_timeout() || why.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// How long to wait before sending the request again after `attempt` attempts
+    /// failed with `why`. Returns `None` if the wait would run past the deadline.
+    pub(crate) fn delay(&self, attempt: u32, why: &Error, elapsed: Duration) -> Option<Duration> {
+        let delay = match why {
+            Error::RateLimited {
+                retry_after: Some(retry_after),
+            } => *retry_after,
+            _ => {
+                let exp = self
+                    .base_delay
+                    .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+                    .min(self.max_delay);
+                if self.jitter {
+                    exp.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+                } else {
+                    exp
+                }
+            }
+        };
+
+        match self.deadline {
+            Some(deadline) if elapsed + delay > deadline => None,
+            _ => Some(delay),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use crate::Error;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use std::time::Duration;
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy::new()
+            .base_delay(Duration::from_millis(1))
+            .jitter(false)
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_secs(1))
+            .max_delay(Duration::from_secs(5))
+            .deadline(Duration::from_secs(10))
+            .jitter(false);
+        let why = Error::Server(reqwest::StatusCode::BAD_GATEWAY);
+
+        assert_eq!(
+            policy.delay(1, &why, Duration::ZERO),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            policy.delay(3, &why, Duration::ZERO),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(
+            policy.delay(8, &why, Duration::ZERO),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(policy.delay(3, &why, Duration::from_secs(8)), None);
+
+        let why = Error::RateLimited {
+            retry_after: Some(Duration::from_secs(7)),
+        };
+        assert_eq!(
+            policy.delay(1, &why, Duration::ZERO),
+            Some(Duration::from_secs(7))
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_reads() {
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/image_2336.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/images/2336"))
+                .times(3)
+                .respond_with(cycle![
+                    status_code(503),
+                    status_code(429).insert_header("Retry-After", "0"),
+                    json_encoded(data),
+                ]),
+        );
+
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .retry_policy(fast())
+            .build()
+            .unwrap();
+        cli.image(2336).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn gives_up() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/v1/json/images/2336"))
+                .times(2)
+                .respond_with(status_code(500)),
+        );
+
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .retry_policy(fast().max_attempts(2))
+            .build()
+            .unwrap();
+        assert!(matches!(cli.image(2336).await, Err(Error::Server(_))));
+    }
+
+    #[tokio::test]
+    async fn never_retries_writes() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/foo.png"))
+                .respond_with(status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/api/v1/json/images"))
+                .times(1)
+                .respond_with(status_code(503)),
+        );
+
+        let cli = crate::Client::builder()
+            .base_url(server.url_str("/"))
+            .key("42069")
+            .retry_policy(fast())
+            .build()
+            .unwrap();
+        let result = cli
+            .post_image(server.url_str("/foo.png"), Default::default())
+            .await;
+        assert!(matches!(result, Err(Error::Server(_))));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{image::ResponseList, upload::Upload, Image, RequestKind, Result};
+use reqwest::multipart::Form;
+
+/// The image to look for with [`Client::reverse_search`](crate::Client::reverse_search).
+#[derive(Debug)]
+pub enum ReverseImage {
+    /// An image the booru can download.
+    Url(String),
+    /// An image file from this machine.
+    File(Upload),
+}
+
+impl From<Upload> for ReverseImage {
+    fn from(file: Upload) -> Self {
+        ReverseImage::File(file)
+    }
+}
+
+impl crate::Client {
+    /// Find images on the booru that look like `image`, such as to check whether an
+    /// image was already uploaded before calling [`Client::post_image`](crate::Client::post_image).
+    ///
+    /// `distance` is how different an image can look and still match, from 0 to 1.
+    /// The booru picks one when it's `None`, 0.25 on Philomena.
+    pub async fn reverse_search<I: Into<ReverseImage>>(
+        &self,
+        image: I,
+        distance: Option<f64>,
+    ) -> Result<Vec<Image>> {
+        let mut req = self.request(reqwest::Method::POST, "api/v1/json/search/reverse");
+        if let Some(distance) = distance {
+            req = req.query(&[("distance", distance.to_string())]);
+        }
+        req = match image.into() {
+            ReverseImage::Url(url) => req.query(&[("url", url)]),
+            ReverseImage::File(file) => req.multipart(Form::new().part("image", file.into_part()?)),
+        };
+
+        // This only reads, even though it has to be POSTed.
+        let resp = self.send_as(req, RequestKind::Read).await?;
+        let resp: ResponseList = crate::decode(resp).await?;
+        Ok(resp.images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReverseImage;
+    use crate::Upload;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    #[tokio::test]
+    async fn reverse_search() {
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/search_images.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/api/v1/json/search/reverse"),
+                request::query(url_decoded(contains((
+                    "url",
+                    "https://example.com/orca.png"
+                )))),
+                request::query(url_decoded(contains(("distance", "0.2")))),
+            ])
+            .respond_with(json_encoded(data.clone())),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/api/v1/json/search/reverse"),
+                request::body(matches(r#"name="image"; filename="orca.png""#)),
+            ])
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let images = cli
+            .reverse_search(
+                ReverseImage::Url("https://example.com/orca.png".into()),
+                Some(0.2),
+            )
+            .await
+            .unwrap();
+        assert_eq!(images.len(), 4);
+
+        let file = Upload::from_bytes(&b"not really a png"[..], "orca.png", "image/png");
+        let images = cli.reverse_search(file, None).await.unwrap();
+        assert_eq!(images.len(), 4);
+    }
+}
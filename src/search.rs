@@ -0,0 +1,387 @@
+/*!
+Typed search requests.
+
+Each search type holds a query string and the paging and sorting parameters that
+Philomena's search endpoints understand. They can be built from a plain query string:
+
+```
+use furbooru::search::{ImageSearch, ImageSortField, SortDirection};
+
+let search = ImageSearch::new("orca, safe")
+    .sort_field(ImageSortField::Score)
+    .sort_direction(SortDirection::Descending)
+    .per_page(50)
+    .filter_id(56027);
+```
+*/
+
+use crate::image::Interaction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How many results are on each page when a search doesn't ask for a page size.
+pub const DEFAULT_PER_PAGE: u64 = 25;
+
+/// The most results the boorus put on a page, whatever page size was asked for.
+pub const MAX_PER_PAGE: u64 = 50;
+
+/// One page of search results along with where it sits in the whole result set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchPage<T> {
+    /// The results on this page.
+    pub items: Vec<T>,
+    /// How many results the search matched in total.
+    pub total: u64,
+    /// The number of this page, starting at 1.
+    pub page: u64,
+    /// How many results are on each page: the page size that was asked for, capped
+    /// at [`MAX_PER_PAGE`] like the booru does.
+    pub per_page: u64,
+    /// The API key owner's interactions with the images on this page. Only image
+    /// searches have these.
+    pub interactions: Option<Vec<Interaction>>,
+}
+
+impl<T> SearchPage<T> {
+    pub(crate) fn new(
+        items: Vec<T>,
+        total: Option<u64>,
+        page: Option<u64>,
+        per_page: Option<u64>,
+        interactions: Option<Vec<Interaction>>,
+    ) -> Self {
+        Self {
+            total: total.unwrap_or(items.len() as u64),
+            items,
+            page: page.filter(|page| *page != 0).unwrap_or(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+            interactions,
+        }
+    }
+
+    /// Returns true if there are no pages of results after this one.
+    pub fn is_last(&self) -> bool {
+        self.items.is_empty() || self.page * self.per_page >= self.total
+    }
+
+    /// How many pages of results the search matched.
+    pub fn page_count(&self) -> u64 {
+        self.total.div_ceil(self.per_page.max(1))
+    }
+}
+
+impl<T> IntoIterator for SearchPage<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+/// Which way results are sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "asc",
+            SortDirection::Descending => "desc",
+        }
+    }
+}
+
+impl fmt::Display for SortDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What image search results are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSortField {
+    /// Upload order.
+    Id,
+    UpdatedAt,
+    FirstSeenAt,
+    AspectRatio,
+    Faves,
+    Upvotes,
+    Downvotes,
+    Score,
+    WilsonScore,
+    /// How well the image matches the query.
+    Relevance,
+    Width,
+    Height,
+    CommentCount,
+    TagCount,
+    Pixels,
+    /// File size.
+    Size,
+    /// Length of animated images and videos.
+    Duration,
+    /// Random order. Searches with the same seed return results in the same order,
+    /// so paging through random results needs a seed.
+    Random {
+        seed: Option<u64>,
+    },
+    /// Position in a gallery. Only images in that gallery have one.
+    GalleryPosition {
+        gallery_id: u64,
+    },
+}
+
+impl fmt::Display for ImageSortField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ImageSortField::Id => "id",
+            ImageSortField::UpdatedAt => "updated_at",
+            ImageSortField::FirstSeenAt => "first_seen_at",
+            ImageSortField::AspectRatio => "aspect_ratio",
+            ImageSortField::Faves => "faves",
+            ImageSortField::Upvotes => "upvotes",
+            ImageSortField::Downvotes => "downvotes",
+            ImageSortField::Score => "score",
+            ImageSortField::WilsonScore => "wilson_score",
+            ImageSortField::Relevance => "_score",
+            ImageSortField::Width => "width",
+            ImageSortField::Height => "height",
+            ImageSortField::CommentCount => "comment_count",
+            ImageSortField::TagCount => "tag_count",
+            ImageSortField::Pixels => "pixels",
+            ImageSortField::Size => "size",
+            ImageSortField::Duration => "duration",
+            ImageSortField::Random { seed: None } => "random",
+            ImageSortField::Random { seed: Some(seed) } => return write!(f, "random:{}", seed),
+            ImageSortField::GalleryPosition { gallery_id } => {
+                return write!(f, "gallery_id:{}", gallery_id)
+            }
+        };
+        f.write_str(name)
+    }
+}
+
+/// An image search request. See the [module documentation](self) for an example.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageSearch {
+    q: String,
+    pub(crate) page: Option<u64>,
+    pub(crate) per_page: Option<u64>,
+    sort_field: Option<ImageSortField>,
+    sort_direction: Option<SortDirection>,
+    filter_id: Option<u64>,
+}
+
+impl ImageSearch {
+    /// Search for images matching a query.
+    pub fn new<T: Into<String>>(q: T) -> Self {
+        Self {
+            q: q.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Search for the images in a gallery, in the gallery's order.
+    ///
+    /// Galleries are shown newest addition first unless their owner changed that,
+    /// and the API doesn't say when they did. Use [`ImageSearch::sort_direction`]
+    /// with [`SortDirection::Ascending`] for galleries shown oldest first.
+    pub fn gallery(gallery_id: u64) -> Self {
+        Self::new(format!("gallery_id:{}", gallery_id))
+            .sort_field(ImageSortField::GalleryPosition { gallery_id })
+            .sort_direction(SortDirection::Descending)
+    }
+
+    /// Fetch this page of results. Pages start at 1.
+    pub fn page(mut self, page: u64) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// Set how many results are on each page. The boorus cap this at 50.
+    pub fn per_page(mut self, per_page: u64) -> Self {
+        self.per_page = Some(per_page);
+        self
+    }
+
+    /// Set what results are sorted by.
+    pub fn sort_field(mut self, field: ImageSortField) -> Self {
+        self.sort_field = Some(field);
+        self
+    }
+
+    /// Set which way results are sorted.
+    pub fn sort_direction(mut self, direction: SortDirection) -> Self {
+        self.sort_direction = Some(direction);
+        self
+    }
+
+    /// Sort results randomly, using `seed` to keep the order stable between pages.
+    pub fn random(self, seed: u64) -> Self {
+        self.sort_field(ImageSortField::Random { seed: Some(seed) })
+    }
+
+    /// Search with this filter instead of the one of the user that owns the API key.
+    pub fn filter_id(mut self, filter_id: u64) -> Self {
+        self.filter_id = Some(filter_id);
+        self
+    }
+
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = paging_params(&self.q, self.page, self.per_page);
+        if let Some(sf) = &self.sort_field {
+            params.push(("sf", sf.to_string()));
+        }
+        if let Some(sd) = &self.sort_direction {
+            params.push(("sd", sd.to_string()));
+        }
+        if let Some(filter_id) = self.filter_id {
+            params.push(("filter_id", filter_id.to_string()));
+        }
+        params
+    }
+}
+
+macro_rules! plain_search {
+    ($($(#[$doc:meta])* $kind:ident => $new_doc:literal;)*) => {
+        $(
+            $(#[$doc])*
+            #[derive(Debug, Clone, Default, PartialEq)]
+            pub struct $kind {
+                q: String,
+                pub(crate) page: Option<u64>,
+                pub(crate) per_page: Option<u64>,
+            }
+
+            impl $kind {
+                #[doc = $new_doc]
+                pub fn new<T: Into<String>>(q: T) -> Self {
+                    Self {
+                        q: q.into(),
+                        ..Self::default()
+                    }
+                }
+
+                /// Fetch this page of results. Pages start at 1.
+                pub fn page(mut self, page: u64) -> Self {
+                    self.page = Some(page);
+                    self
+                }
+
+                /// Set how many results are on each page. The boorus cap this at 50.
+                pub fn per_page(mut self, per_page: u64) -> Self {
+                    self.per_page = Some(per_page);
+                    self
+                }
+
+                pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+                    paging_params(&self.q, self.page, self.per_page)
+                }
+            }
+        )*
+    };
+}
+
+plain_search! {
+    /// A tag search request.
+    TagSearch => "Search for tags matching a query.";
+    /// A comment search request.
+    CommentSearch => "Search for comments matching a query.";
+    /// A gallery search request.
+    GallerySearch => "Search for galleries matching a query.";
+    /// A forum post search request.
+    PostSearch => "Search for forum posts matching a query.";
+}
+
+fn paging_params(q: &str, page: Option<u64>, per_page: Option<u64>) -> Vec<(&'static str, String)> {
+    let mut params = vec![("q", q.to_string())];
+    if let Some(page) = page.filter(|page| *page != 0) {
+        params.push(("page", page.to_string()));
+    }
+    if let Some(per_page) = per_page {
+        params.push(("per_page", per_page.to_string()));
+    }
+    params
+}
+
+macro_rules! from_query {
+    ($($kind:ty),*) => {
+        $(
+            impl From<&str> for $kind {
+                fn from(q: &str) -> Self {
+                    Self::new(q)
+                }
+            }
+
+            impl From<String> for $kind {
+                fn from(q: String) -> Self {
+                    Self::new(q)
+                }
+            }
+        )*
+    };
+}
+
+from_query!(
+    ImageSearch,
+    TagSearch,
+    CommentSearch,
+    GallerySearch,
+    PostSearch
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_search_params() {
+        let search = ImageSearch::new("orca")
+            .page(2)
+            .per_page(50)
+            .random(1234)
+            .sort_direction(SortDirection::Ascending)
+            .filter_id(56027);
+
+        assert_eq!(
+            search.params(),
+            vec![
+                ("q", "orca".to_string()),
+                ("page", "2".to_string()),
+                ("per_page", "50".to_string()),
+                ("sf", "random:1234".to_string()),
+                ("sd", "asc".to_string()),
+                ("filter_id", "56027".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn search_page() {
+        let page = SearchPage::new(vec![1; 25], Some(254), Some(10), None, None);
+        assert_eq!(page.page_count(), 11);
+        assert!(!page.is_last());
+
+        let page = SearchPage::new(vec![1; 4], Some(254), Some(11), None, None);
+        assert!(page.is_last());
+
+        // asking for more than the booru allows still gets pages of 50
+        let page = SearchPage::new(vec![1; 50], Some(254), Some(3), Some(100), None);
+        assert_eq!(page.per_page, 50);
+        assert_eq!(page.page_count(), 6);
+        assert!(!page.is_last());
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            TagSearch::from("artist:*").params(),
+            vec![("q", "artist:*".to_string())]
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use crate::Result;
+use crate::{
+    paginate::paginate,
+    search::{SearchPage, TagSearch},
+    Extra, Result,
+};
+use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,52 +14,96 @@ struct Response {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct ResponseList {
     pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub total: Option<u64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Tag {
     pub aliased_tag: Option<String>,
     pub aliases: Option<Vec<String>>,
     pub category: String,
     pub description: Option<String>,
-    pub dnp_entries: Vec<::serde_json::Value>, // TODO(Xe): update this when furbooru has a DNP entry
+    pub dnp_entries: Vec<DnpEntry>,
     pub id: i64,
     pub images: i64,
     pub implied_by_tags: Option<Vec<String>>,
-    pub implied_tags: Vec<::serde_json::Value>,
+    pub implied_tags: Vec<String>,
     pub name: String,
     pub name_in_namespace: String,
     pub namespace: Option<String>,
     pub short_description: Option<String>,
     pub slug: String,
     pub spoiler_image_uri: Option<String>,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
+}
+
+/// A "do not post" request from an artist, limiting what can be posted of their art.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct DnpEntry {
+    /// What the artist allows, such as "edits are fine with credit".
+    pub conditions: String,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
 }
 
 impl crate::Client {
     pub async fn tag<T: Into<String>>(&self, name: T) -> Result<Tag> {
         let name = name.into().replace(":", "-colon-");
         let resp: Response = self
-            .request(reqwest::Method::GET, &format!("api/v1/json/tags/{}", name))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            .execute(self.request(reqwest::Method::GET, &format!("api/v1/json/tags/{}", name)))
             .await?;
         Ok(resp.tag)
     }
 
     pub async fn tag_search<T: Into<String>>(&self, q: T, page: u64) -> Result<Vec<Tag>> {
-        let mut req = self
-            .request(reqwest::Method::GET, &format!("api/v1/json/search/tags"))
-            .query(&[("q", q.into())]);
+        Ok(self.search_tags(TagSearch::new(q).page(page)).await?.items)
+    }
 
-        if page != 0 {
-            req = req.query(&[("page", format!("{}", page))]);
-        }
+    /// Search for tags with paging options.
+    pub async fn search_tags<S: Into<TagSearch>>(&self, search: S) -> Result<SearchPage<Tag>> {
+        let search = search.into();
+        let resp = self.tag_search_page(&search).await?;
+        Ok(SearchPage::new(
+            resp.tags,
+            resp.total,
+            search.page,
+            search.per_page,
+            None,
+        ))
+    }
+
+    /// Search for tags, lazily fetching every page of results starting at the
+    /// search's page.
+    pub fn tag_search_stream<S: Into<TagSearch>>(
+        &self,
+        search: S,
+    ) -> impl Stream<Item = Result<Tag>> + '_ {
+        let search = search.into();
+        paginate(search.page.unwrap_or(1), search.per_page, move |page| {
+            let search = search.clone().page(page);
+            async move {
+                let resp = self.tag_search_page(&search).await?;
+                Ok((resp.tags, resp.total))
+            }
+        })
+    }
 
-        let resp: ResponseList = req.send().await?.error_for_status()?.json().await?;
+    async fn tag_search_page(&self, search: &TagSearch) -> Result<ResponseList> {
+        let req = self
+            .request(reqwest::Method::GET, "api/v1/json/search/tags")
+            .query(&search.params());
 
-        Ok(resp.tags)
+        self.execute(req).await
     }
 }
 
@@ -95,6 +144,8 @@ mod tests {
 
         let cli =
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
-        cli.tag_search("orca", 0).await.unwrap();
+        let tags = cli.tag_search("orca", 0).await.unwrap();
+        assert_eq!(tags[0].implied_tags, vec!["mammal", "cetacean"]);
+        assert!(tags[0].dnp_entries.is_empty());
     }
 }
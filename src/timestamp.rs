@@ -0,0 +1,134 @@
+/*!
+The type of the timestamps on models, such as [`Image::created_at`](crate::Image::created_at).
+
+By default timestamps are kept as the strings the booru sends, like
+`2020-06-11T03:43:32`. Turn on the `chrono` feature to get
+[`chrono::DateTime<Utc>`](https://docs.rs/chrono) instead:
+
+```toml
+furbooru = { version = "0.4", features = ["chrono"] }
+```
+
+The boorus send some timestamps without a UTC offset. Those are in UTC.
+*/
+
+/// A point in time sent by the booru.
+#[cfg(not(feature = "chrono"))]
+pub type Timestamp = String;
+
+/// A point in time sent by the booru.
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
+
+/// Seconds since the unix epoch of a timestamp, if it is one.
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn unix(ts: &Timestamp) -> Option<i64> {
+    crate::matcher::parse_date(ts, 0).map(|(start, _)| start)
+}
+
+/// Seconds since the unix epoch of a timestamp, if it is one.
+#[cfg(feature = "chrono")]
+pub(crate) fn unix(ts: &Timestamp) -> Option<i64> {
+    Some(ts.timestamp())
+}
+
+#[cfg(not(feature = "chrono"))]
+pub(crate) use serde_string::*;
+
+#[cfg(feature = "chrono")]
+pub(crate) use serde_chrono::*;
+
+#[cfg(not(feature = "chrono"))]
+mod serde_string {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(ts: &str, s: S) -> Result<S::Ok, S::Error> {
+        ts.serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+        String::deserialize(d)
+    }
+
+    pub(crate) mod option {
+        use super::*;
+
+        pub(crate) fn serialize<S: Serializer>(
+            ts: &Option<String>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            ts.serialize(s)
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            d: D,
+        ) -> Result<Option<String>, D::Error> {
+            Option::deserialize(d)
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod serde_chrono {
+    use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    fn parse(text: &str) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(text)
+            .map(|ts| ts.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").map(|ts| ts.and_utc())
+            })
+            .ok()
+    }
+
+    pub(crate) fn serialize<S: Serializer>(ts: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&ts.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<DateTime<Utc>, D::Error> {
+        let text = String::deserialize(d)?;
+        parse(&text).ok_or_else(|| D::Error::custom(format!("invalid timestamp {:?}", text)))
+    }
+
+    pub(crate) mod option {
+        use super::*;
+
+        pub(crate) fn serialize<S: Serializer>(
+            ts: &Option<DateTime<Utc>>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            match ts {
+                Some(ts) => super::serialize(ts, s),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            d: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            match Option::<String>::deserialize(d)? {
+                Some(text) => parse(&text)
+                    .map(Some)
+                    .ok_or_else(|| D::Error::custom(format!("invalid timestamp {:?}", text))),
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Image;
+
+    #[test]
+    fn timestamps() {
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/image_2336.json")).unwrap();
+        let img: Image = serde_json::from_value(data["image"].clone()).unwrap();
+        assert_eq!(super::unix(&img.created_at), Some(1591847012));
+
+        let again: Image = serde_json::from_value(serde_json::to_value(&img).unwrap()).unwrap();
+        assert_eq!(again.created_at, img.created_at);
+    }
+}
@@ -1,20 +1,26 @@
-use crate::Result;
+use crate::{paginate::paginate, timestamp::Timestamp, Extra, Result};
+use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct ResponseList {
-    topics: Vec<Topic>,
+struct Response {
+    topic: Topic,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Response {
-    topic: Topic,
+struct ResponseList {
+    topics: Vec<Topic>,
+    #[serde(default)]
+    total: Option<u64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "strict"), serde(default))]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Topic {
     pub author: String,
-    pub last_replied_to_at: Option<String>,
+    #[serde(with = "crate::timestamp::option")]
+    pub last_replied_to_at: Option<Timestamp>,
     pub locked: bool,
     pub post_count: i64,
     pub slug: String,
@@ -22,26 +28,64 @@ pub struct Topic {
     pub title: String,
     pub user_id: i64,
     pub view_count: i64,
+    /// Fields this version of the crate doesn't know about.
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: Extra,
 }
 
 impl crate::Client {
     pub async fn topic<T: Into<String>>(&self, forum: T, thread: T) -> Result<Topic> {
         let resp: Response = self
-            .request(
+            .execute(self.request(
                 reqwest::Method::GET,
                 &format!(
                     "api/v1/json/forums/{}/topics/{}",
                     forum.into(),
                     thread.into()
                 ),
-            )
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
+            ))
             .await?;
         Ok(resp.topic)
     }
+
+    /// Fetches page n of the topics in a forum, sticky topics first, then by when
+    /// they were last replied to.
+    pub async fn forum_topics<T: Into<String>>(&self, forum: T, page: u64) -> Result<Vec<Topic>> {
+        let path = topics_path(&forum.into());
+        Ok(self.forum_topics_page(&path, page).await?.topics)
+    }
+
+    /// Fetches every topic in a forum, lazily fetching each page starting at the one
+    /// [`Client::forum_topics`](crate::Client::forum_topics) would fetch.
+    pub fn forum_topics_stream<T: Into<String>>(
+        &self,
+        forum: T,
+        page: u64,
+    ) -> impl Stream<Item = Result<Topic>> + '_ {
+        let path = topics_path(&forum.into());
+        paginate(page, None, move |page| {
+            let path = path.clone();
+            async move {
+                let resp = self.forum_topics_page(&path, page).await?;
+                Ok((resp.topics, resp.total))
+            }
+        })
+    }
+
+    async fn forum_topics_page(&self, path: &str, page: u64) -> Result<ResponseList> {
+        let mut req = self.request(reqwest::Method::GET, path);
+
+        if page != 0 {
+            req = req.query(&[("page", format!("{}", page))])
+        }
+
+        self.execute(req).await
+    }
+}
+
+fn topics_path(forum: &str) -> String {
+    format!("api/v1/json/forums/{}/topics", forum)
 }
 
 #[cfg(test)]
@@ -67,4 +111,45 @@ mod tests {
             crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
         cli.topic("dis", "ask-the-mods-anything").await.unwrap();
     }
+
+    #[tokio::test]
+    async fn forum_topics_stream() {
+        use futures_util::TryStreamExt;
+
+        let _ = pretty_env_logger::try_init();
+        // There's no captured topic listing in testdata, so list the one real topic we
+        // have, followed by an empty page to end the stream.
+        let topic: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/forum_dis_topic_amamods.json"))
+                .unwrap();
+        let data = serde_json::json!({ "topics": [topic["topic"]] });
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/forums/dis/topics"),
+                request::query(url_decoded(contains(("page", "1")))),
+            ])
+            .times(1)
+            .respond_with(json_encoded(data)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/v1/json/forums/dis/topics"),
+                request::query(url_decoded(contains(("page", "2")))),
+            ])
+            .times(1)
+            .respond_with(json_encoded(serde_json::json!({ "topics": [] }))),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let topics: Vec<crate::Topic> = cli
+            .forum_topics_stream("dis", 1)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(topics.len(), 1);
+        assert_eq!(topics[0].slug, "ask-the-mods-anything");
+        assert!(topics[0].sticky);
+    }
 }
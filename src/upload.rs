@@ -0,0 +1,245 @@
+use crate::{image::Response, Error, Image, ImageMeta, Result};
+use reqwest::{
+    multipart::{Form, Part},
+    Body, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+use tokio::io::AsyncRead;
+use tokio_util::io::ReaderStream;
+
+/// A local image file to upload with [`Client::upload_image`](crate::Client::upload_image).
+#[derive(Debug)]
+pub struct Upload {
+    body: Body,
+    len: Option<u64>,
+    file_name: String,
+    mime_type: String,
+}
+
+impl Upload {
+    /// Upload an image that is already in memory.
+    pub fn from_bytes<B, N, M>(data: B, file_name: N, mime_type: M) -> Self
+    where
+        B: Into<Vec<u8>>,
+        N: Into<String>,
+        M: Into<String>,
+    {
+        let data = data.into();
+        Self {
+            len: Some(data.len() as u64),
+            body: data.into(),
+            file_name: file_name.into(),
+            mime_type: mime_type.into(),
+        }
+    }
+
+    /// Upload an image read from `reader`, such as a [`tokio::fs::File`]. The image
+    /// is streamed to the booru without being read into memory first.
+    pub fn from_reader<R, N, M>(reader: R, file_name: N, mime_type: M) -> Self
+    where
+        R: AsyncRead + Send + Sync + 'static,
+        N: Into<String>,
+        M: Into<String>,
+    {
+        Self {
+            body: Body::wrap_stream(ReaderStream::new(reader)),
+            len: None,
+            file_name: file_name.into(),
+            mime_type: mime_type.into(),
+        }
+    }
+
+    /// Tell the booru how big the image is up front. Some boorus reject streamed
+    /// uploads without a length.
+    pub fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    pub(crate) fn into_part(self) -> Result<Part> {
+        let part = match self.len {
+            Some(len) => Part::stream_with_length(self.body, len),
+            None => Part::stream(self.body),
+        };
+        Ok(part.file_name(self.file_name).mime_str(&self.mime_type)?)
+    }
+
+    fn into_form(self, meta: ImageMeta) -> Result<Form> {
+        Ok(Form::new()
+            .text("image[description]", meta.description)
+            .text("image[tag_input]", meta.tag_input)
+            .text("image[source_url]", meta.source_url)
+            .part("image[image]", self.into_part()?))
+    }
+}
+
+/// The reasons the booru gave for rejecting an upload, by the name of the field they
+/// are about, such as `image`, `tag_input` or `source_url`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadError {
+    pub errors: BTreeMap<String, Vec<String>>,
+}
+
+impl UploadError {
+    /// The messages about one field.
+    pub fn field(&self, name: &str) -> &[String] {
+        self.errors.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns true if the booru already has this image.
+    pub fn is_duplicate(&self) -> bool {
+        self.any(|field, message| {
+            field.contains("sha512_hash")
+                || (field == "image" && message.contains("already"))
+                || message.contains("duplicate")
+        })
+    }
+
+    /// Returns true if the tags have no rating tag, like `safe` or `explicit`.
+    pub fn is_missing_rating(&self) -> bool {
+        self.any(|field, message| field == "tag_input" && message.contains("rating"))
+    }
+
+    /// Returns true if the image file or its dimensions are bigger than the booru
+    /// allows.
+    pub fn is_too_large(&self) -> bool {
+        self.any(|field, message| {
+            matches!(field, "image_size" | "image_width" | "image_height")
+                || message.contains("too large")
+                || message.contains("cannot be more than")
+        })
+    }
+
+    fn any<F: Fn(&str, &str) -> bool>(&self, f: F) -> bool {
+        self.errors.iter().any(|(field, messages)| {
+            messages
+                .iter()
+                .any(|message| f(field, &message.to_lowercase()))
+        })
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (field, messages) in &self.errors {
+            for message in messages {
+                if !first {
+                    f.write_str("; ")?;
+                }
+                first = false;
+                write!(f, "{} {}", field, message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+/// Decode the booru's answer to an upload, turning validation failures into
+/// [`Error::Upload`].
+pub(crate) async fn upload_response(resp: reqwest::Response) -> Result<Image> {
+    let status = resp.status();
+    // This usually comes from a proxy in front of the booru, not the booru itself.
+    if status == StatusCode::PAYLOAD_TOO_LARGE {
+        let mut errors = BTreeMap::new();
+        errors.insert("image".to_string(), vec!["is too large".to_string()]);
+        return Err(Error::Upload(UploadError { errors }));
+    }
+    if status == StatusCode::BAD_REQUEST || status == StatusCode::UNPROCESSABLE_ENTITY {
+        let why = Error::from_response(&resp);
+        let body = resp.text().await?;
+        return match serde_json::from_str::<UploadError>(&body) {
+            Ok(errors) => Err(Error::Upload(errors)),
+            Err(_) => {
+                log::debug!("body: {}", body);
+                Err(why)
+            }
+        };
+    }
+
+    let resp: Response = crate::decode(resp).await?;
+    Ok(resp.image)
+}
+
+impl crate::Client {
+    /// Upload an image file from this machine, for images that aren't hosted
+    /// anywhere the booru can fetch them from. This needs an API key.
+    ///
+    /// If the booru rejects the upload, this fails with [`Error::Upload`] listing
+    /// what was wrong with it. Like [`Client::post_image`](crate::Client::post_image),
+    /// test this with a custom instance of philomena first.
+    pub async fn upload_image(&self, file: Upload, meta: ImageMeta) -> Result<Image> {
+        self.require_key()?;
+
+        let form = file.into_form(meta)?;
+        let resp = self
+            .send(
+                self.request(reqwest::Method::POST, "api/v1/json/images")
+                    .multipart(form),
+            )
+            .await?;
+        upload_response(resp).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Upload;
+    use crate::{Error, ImageMeta};
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    #[tokio::test]
+    async fn upload_image() {
+        let _ = pretty_env_logger::try_init();
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/image_2336.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/api/v1/json/images"),
+                request::body(matches(r#"name="image\[image\]"; filename="orca.png""#)),
+                request::body(matches("safe, orca")),
+                request::body(matches("not really a png")),
+            ])
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let meta = ImageMeta {
+            tag_input: "safe, orca".into(),
+            ..ImageMeta::default()
+        };
+        let file = Upload::from_reader(&b"not really a png"[..], "orca.png", "image/png");
+        cli.upload_image(file, meta).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn upload_rejected() {
+        let _ = pretty_env_logger::try_init();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/api/v1/json/images"))
+                .respond_with(status_code(400).body(
+                    r#"{"errors":{"image":["can't be blank"],"tag_input":["must contain at least 3 tags"]}}"#,
+                )),
+        );
+
+        let cli =
+            crate::Client::with_baseurl("test", "42069", &format!("{}", server.url("/"))).unwrap();
+        let file = Upload::from_bytes(Vec::new(), "orca.png", "image/png");
+        match cli.upload_image(file, ImageMeta::default()).await {
+            Err(Error::Upload(why)) => {
+                assert_eq!(why.field("image"), &["can't be blank".to_string()]);
+                assert_eq!(why.field("tag_input").len(), 1);
+                assert!(why.field("source_url").is_empty());
+                assert!(!why.is_duplicate());
+                assert!(!why.is_too_large());
+            }
+            result => panic!("wanted an upload error, got {:?}", result),
+        }
+    }
+}